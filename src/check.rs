@@ -0,0 +1,328 @@
+use anyhow::Result;
+use std::{fs, path::Path};
+use toml_edit::{ImDocument, Item, Table};
+
+use crate::config::{
+    APPLICATION_KEYS, Config, DesktopFileConfig, Location, MATCHERS_KEYS, REWRITE_KEYS, ROOT_KEYS,
+    RULE_KEYS, SUBSTITUTION_KEYS,
+};
+use crate::desktop_files::resolve_desktop_file;
+use crate::profiles::{ProfilesKind, discover_profiles};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    /// 1-based line in the config file
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Loads the config file and reports every problem found, without starting GTK nor D-Bus.
+pub fn check_config(config_path: &Path) -> Result<Vec<Problem>> {
+    let content = fs::read_to_string(config_path)?;
+    let mut problems = Vec::new();
+
+    let document = match ImDocument::parse(content.as_str()) {
+        Ok(document) => document,
+        Err(e) => {
+            problems.push(Problem {
+                severity: Severity::Error,
+                line: e.span().map(|span| line_of(&content, span.start)),
+                message: e.message().to_string(),
+            });
+            return Ok(problems);
+        }
+    };
+    let lines = Lines {
+        content: &content,
+        document: &document,
+    };
+
+    check_unknown_keys(&lines, &mut problems);
+
+    let (config, errors) = match Config::parse_unchecked(&content) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let span = e.downcast_ref::<toml::de::Error>().and_then(|e| e.span());
+            problems.push(Problem {
+                severity: Severity::Error,
+                line: span.map(|span| line_of(&content, span.start)),
+                message: e
+                    .downcast_ref::<toml::de::Error>()
+                    .map_or_else(|| e.to_string(), |e| e.message().to_string()),
+            });
+            return Ok(problems);
+        }
+    };
+    for error in errors {
+        problems.push(Problem {
+            severity: Severity::Error,
+            line: lines.of_location(error.location),
+            message: error.to_string(),
+        });
+    }
+
+    check_desktop_files(&config, &lines, &mut problems);
+    check_unreachable_rules(&config, &lines, &mut problems);
+
+    problems.sort_by_key(|p| p.line);
+    Ok(problems)
+}
+
+struct Lines<'a> {
+    content: &'a str,
+    document: &'a ImDocument<&'a str>,
+}
+
+impl Lines<'_> {
+    fn of_location(&self, location: Location) -> Option<usize> {
+        let table = match location {
+            Location::Root => return None,
+            Location::Application(index) => self.table("application", index),
+            Location::Rule(index) => self.table("rule", index),
+            Location::Substitution(index) => self
+                .document
+                .get("rewrite")
+                .and_then(|rewrite| rewrite.get("substitution"))
+                .and_then(Item::as_array_of_tables)
+                .and_then(|tables| tables.get(index)),
+        }?;
+        table.span().map(|span| line_of(self.content, span.start))
+    }
+
+    fn table(&self, name: &str, index: usize) -> Option<&Table> {
+        self.document
+            .get(name)
+            .and_then(Item::as_array_of_tables)
+            .and_then(|tables| tables.get(index))
+    }
+}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+fn check_unknown_keys(lines: &Lines, problems: &mut Vec<Problem>) {
+    let mut check_table = |table: &Table, known: &[&[&str]], context: &str| {
+        for (key, _) in table.iter() {
+            if known.iter().any(|keys| keys.contains(&key)) {
+                continue;
+            }
+            problems.push(Problem {
+                severity: Severity::Error,
+                line: table
+                    .key(key)
+                    .and_then(|k| k.span())
+                    .map(|span| line_of(lines.content, span.start)),
+                message: format!("{}: unknown key `{}`", context, key),
+            });
+        }
+    };
+
+    check_table(lines.document.as_table(), &[ROOT_KEYS], "config");
+    for (name, known) in [
+        ("application", [APPLICATION_KEYS, MATCHERS_KEYS]),
+        ("rule", [RULE_KEYS, MATCHERS_KEYS]),
+    ] {
+        let Some(tables) = lines.document.get(name).and_then(Item::as_array_of_tables) else {
+            continue;
+        };
+        for (index, table) in tables.iter().enumerate() {
+            check_table(table, &known, &format!("{} #{}", name, index + 1));
+        }
+    }
+    if let Some(rewrite) = lines.document.get("rewrite").and_then(Item::as_table) {
+        check_table(rewrite, &[REWRITE_KEYS], "rewrite");
+        let substitutions = rewrite
+            .get("substitution")
+            .and_then(Item::as_array_of_tables);
+        for (index, table) in substitutions.iter().flat_map(|t| t.iter()).enumerate() {
+            check_table(
+                table,
+                &[SUBSTITUTION_KEYS],
+                &format!("substitution #{}", index + 1),
+            );
+        }
+    }
+}
+
+fn check_desktop_files(config: &Config, lines: &Lines, problems: &mut Vec<Problem>) {
+    for (index, desktop_file) in config.desktop_files.iter().enumerate() {
+        let location = Location::Application(index);
+        // an empty id is already reported as a config error
+        let resolved = if desktop_file.id.is_empty() {
+            None
+        } else {
+            Some(resolve_desktop_file(desktop_file))
+        };
+        match resolved {
+            Some(None) => problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!("{}: desktop file not found: {}", location, desktop_file.id),
+            }),
+            Some(Some(app_info)) => {
+                // actions set on the application and on the rules launching it
+                let actions = config
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.desktop_file_id == desktop_file.id)
+                    .filter_map(|rule| rule.action.as_ref().map(|action| (rule.source, action)))
+                    .chain(
+                        desktop_file
+                            .action
+                            .as_ref()
+                            .map(|action| (location, action)),
+                    );
+                let known_actions = app_info.list_actions();
+                for (location, action) in actions {
+                    if !known_actions.iter().any(|a| a == action) {
+                        problems.push(Problem {
+                            severity: Severity::Error,
+                            line: lines.of_location(location),
+                            message: format!(
+                                "{}: unknown desktop action '{}' for {}",
+                                location, action, desktop_file.id
+                            ),
+                        });
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if let Some(alias) = &desktop_file.alias
+            && config.desktop_files[..index]
+                .iter()
+                .any(|df| df.alias.as_ref() == Some(alias))
+        {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!("{}: duplicate alias: {}", location, alias),
+            });
+        }
+
+        if let Some(kind) = desktop_file.profiles {
+            check_profiles(config, desktop_file, kind, location, lines, problems);
+        }
+    }
+}
+
+fn check_profiles(
+    config: &Config,
+    desktop_file: &DesktopFileConfig,
+    kind: ProfilesKind,
+    location: Location,
+    lines: &Lines,
+    problems: &mut Vec<Problem>,
+) {
+    let profiles = match discover_profiles(kind, desktop_file.profiles_dir.as_deref()) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!("{}: failed to list profiles: {}", location, e),
+            });
+            return;
+        }
+    };
+    let rules = config
+        .rules
+        .iter()
+        .filter(|rule| rule.desktop_file_id == desktop_file.id);
+    for rule in rules {
+        if let Some(profile) = &rule.profile
+            && !profiles.iter().any(|p| &p.id == profile)
+        {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(rule.source),
+                message: format!(
+                    "{}: unknown profile '{}' for {}",
+                    rule.source, profile, desktop_file.id
+                ),
+            });
+        }
+    }
+}
+
+fn check_unreachable_rules(config: &Config, lines: &Lines, problems: &mut Vec<Problem>) {
+    let mut default_rule: Option<Location> = None;
+    // prefixes of the previous rules, with the rule defining them
+    let mut previous_prefixes: Vec<(&str, Location)> = Vec::new();
+
+    for rule in &config.rules {
+        let location = rule.source;
+        if let Some(default_location) = default_rule {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!(
+                    "{}: never evaluated, {} is a default rule",
+                    location, default_location
+                ),
+            });
+            continue;
+        }
+        if rule.is_default() {
+            // scheduled default rules do not always apply
+            if rule.is_terminal_default() {
+                default_rule = Some(location);
+            }
+            continue;
+        }
+
+        let prefixes = rule.matchers.prefixes.as_deref().unwrap_or_default();
+        let mut shadowed = 0;
+        for prefix in prefixes {
+            let Some((_, shadowed_by)) = previous_prefixes
+                .iter()
+                .find(|(previous, _)| prefix.starts_with(previous))
+            else {
+                continue;
+            };
+            shadowed += 1;
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!(
+                    "{}: prefix '{}' is shadowed by {}",
+                    location, prefix, shadowed_by
+                ),
+            });
+        }
+        if shadowed > 0
+            && shadowed == prefixes.len()
+            && rule.regex_range.is_empty()
+            && !rule.matchers.has_url_matchers()
+        {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                line: lines.of_location(location),
+                message: format!("{}: unreachable, every prefix is shadowed", location),
+            });
+        }
+
+        // with exclusions, a prefix does not shadow everything after it
+        if !rule.matchers.has_exclusions() {
+            previous_prefixes.extend(prefixes.iter().map(|p| (p.as_str(), location)));
+        }
+    }
+}
@@ -6,16 +6,16 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
 
-    #[arg(index = 1, help = "URI to open")]
-    pub uri: Option<String>,
+    #[arg(index = 1, help = "URIs to open")]
+    pub uris: Vec<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     Daemon {
-        /// Set the default application index on fallback
+        /// Set the default application on fallback, by alias, id or index in the status
         #[arg(long)]
-        set_default: Option<u64>,
+        set_default: Option<String>,
 
         /// Unset the default application on fallback, will open the UI instead
         #[arg(long, required = false)]
@@ -35,10 +35,55 @@ pub enum Commands {
         #[arg(long, required = false)]
         set_default_next: bool,
 
+        /// Set the previous default application index
+        /// If no default application is set yet, this will set the last application as default
+        /// If this is the first application, it will unset the default application
+        #[arg(long, required = false)]
+        set_default_prev: bool,
+
+        /// Reload config.toml and style.css, the current config is kept if the new one is invalid
+        #[arg(long, required = false)]
+        reload: bool,
+
         /// Waybar helper
         #[arg(long, required = false)]
         waybar: bool,
     },
+
+    /// Generate the config file from installed browsers, never overwrites an existing one
+    Init,
+
+    /// Config file helpers
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Show which rule matches an uri and why, nothing is launched
+    Explain {
+        /// URI to explain
+        uri: String,
+
+        /// Print the explanation in JSON format
+        #[arg(long, required = false)]
+        json: bool,
+    },
+
+    /// Install the desktop entry and set choosme as the default browser
+    Install,
+
+    /// Remove the desktop entry and restore the previous default browser
+    Uninstall,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Report every problem of the config file, exits with an error if any
+    Check {
+        /// Warnings are errors too
+        #[arg(long, required = false)]
+        strict: bool,
+    },
 }
 
 pub fn parse() -> Cli {
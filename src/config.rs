@@ -1,10 +1,16 @@
-use anyhow::Result;
-use regex::Regex;
-use serde::Deserialize;
-use std::{env, fs, io};
+use anyhow::{Result, format_err};
+use chrono::NaiveDateTime;
+use regex::{Regex, RegexSet, SetMatches};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, io, ops::Range, path::PathBuf};
 use tracing::info;
+use url::Url;
 use xdg::BaseDirectories;
 
+use crate::desktop_files::{guess_mime_type, scheme_handler};
+use crate::profiles::ProfilesKind;
+use crate::schedule::Schedule;
+
 pub fn read_css_file() -> Result<String> {
     let xdg_dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
     let css_path = xdg_dirs.place_config_file("style.css")?;
@@ -23,73 +29,1408 @@ pub fn read_css_file() -> Result<String> {
     }
 }
 
+/// Keys accepted in each table, anything else is reported by `choosme config check`.
+pub const ROOT_KEYS: &[&str] = &["application", "rule", "rewrite"];
+pub const MATCHERS_KEYS: &[&str] = &[
+    "prefixes",
+    "regexps",
+    "hosts",
+    "schemes",
+    "path_prefixes",
+    "queries",
+    "mime_types",
+    "exclude_prefixes",
+    "exclude_regexps",
+    "exclude_hosts",
+    "exclude_path_prefixes",
+];
+pub const APPLICATION_KEYS: &[&str] = &[
+    "desktop_id",
+    "path",
+    "name",
+    "alias",
+    "key",
+    "args",
+    "action",
+    "expand_actions",
+    "profiles",
+    "profiles_dir",
+];
+pub const RULE_KEYS: &[&str] = &[
+    "application",
+    "default",
+    "args",
+    "action",
+    "days",
+    "from",
+    "to",
+];
+pub const REWRITE_KEYS: &[&str] = &["unwrap_redirects", "strip_params", "substitution"];
+pub const SUBSTITUTION_KEYS: &[&str] = &["regexp", "replace"];
+
+/// Where something is defined in the config file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Location {
+    #[default]
+    Root,
+    /// index of the `[[application]]` table
+    Application(usize),
+    /// index of the `[[rule]]` table
+    Rule(usize),
+    /// index of the `[[rewrite.substitution]]` table
+    Substitution(usize),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Root => write!(f, "config"),
+            Location::Application(index) => write!(f, "application #{}", index + 1),
+            Location::Rule(index) => write!(f, "rule #{}", index + 1),
+            Location::Substitution(index) => write!(f, "substitution #{}", index + 1),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigError {
+    pub location: Location,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(location: Location, message: impl Into<String>) -> Self {
+        Self {
+            location,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// An uri matches if any prefix matches, or any regexp matches,
+/// or if every structured matcher set (hosts, schemes, path_prefixes, queries, mime_types) matches.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Matchers {
+    /// matched against the raw uri
+    pub prefixes: Option<Vec<String>>,
+    pub regexps: Option<Vec<String>>,
+    /// `example.com`, or `*.example.com` for example.com and all its subdomains
+    pub hosts: Option<Vec<String>>,
+    /// `https`, `mailto`, `zoommtg`...
+    pub schemes: Option<Vec<String>>,
+    /// `/fabienjuif/`
+    pub path_prefixes: Option<Vec<String>>,
+    /// `key` if the query parameter has to be present, `key=value` for a given value
+    pub queries: Option<Vec<String>>,
+    /// `application/pdf`, or `image/*`, guessed from the file name of `file://` uris
+    pub mime_types: Option<Vec<String>>,
+    /// checked after a positive match, any exclusion matching makes the rule not match
+    pub exclude_prefixes: Option<Vec<String>>,
+    pub exclude_regexps: Option<Vec<String>>,
+    pub exclude_hosts: Option<Vec<String>>,
+    pub exclude_path_prefixes: Option<Vec<String>>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct DesktopFileConfig {
     /// used to identify the desktop file in the config
-    /// this is either the path or the name
+    /// this is either the desktop id or the path
     /// this is for internal use only, not displayed to the user
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub id: String,
-    // TODO: make path optional, and just resolve by name
-    pub path: String,
+    /// desktop file ID (eg: `firefox.desktop`), resolved through the XDG data dirs
+    pub desktop_id: Option<String>,
+    /// absolute path to the desktop file, `~/` is expanded
+    pub path: Option<String>,
+    /// name used by `[[rule]]` to reference this application
+    pub name: Option<String>,
     /// if set, this name is printed instead of the one in the desktop file
     pub alias: Option<String>,
-    pub prefixes: Option<Vec<String>>,
-    pub regexps: Option<Vec<String>>,
+    /// letter opening this application from the UI, with Shift its private window action
+    pub key: Option<String>,
+    /// extra arguments given to the application on launch, eg: `["-P", "work"]`
+    pub args: Option<Vec<String>>,
+    /// desktop action to launch instead of the main entry, eg: `new-private-window`
+    pub action: Option<String>,
+    /// if true, each desktop action is also offered as a choice, eg: `firefox.desktop:new-private-window`
+    pub expand_actions: Option<bool>,
+    /// if set, each browser profile is also offered as a choice, eg: `firefox.desktop:work`
+    pub profiles: Option<ProfilesKind>,
+    /// where `profiles.ini` or `Local State` is, defaults to the browser directory
+    pub profiles_dir: Option<String>,
+    /// legacy matchers, they are turned into rules at load time
+    #[serde(flatten)]
+    pub matchers: Matchers,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RuleConfig {
+    /// name of the application to launch when this rule matches
+    pub application: Option<String>,
+    /// if set, this rule matches every uri and launches the given application
+    /// rules after this one are never evaluated
+    pub default: Option<String>,
+    /// appended to the application `args`
+    pub args: Option<Vec<String>>,
+    /// replaces the application `action`
+    pub action: Option<String>,
+    /// default rules only apply on these days, eg: `["weekdays"]`, `["sat", "sun"]`
+    pub days: Option<Vec<String>>,
+    /// default rules only apply from this time, `HH:MM`
+    pub from: Option<String>,
+    /// default rules only apply until this time, `HH:MM`
+    pub to: Option<String>,
+    #[serde(flatten)]
+    pub matchers: Matchers,
+    /// parsed from `days`, `from` and `to`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub schedule: Option<Schedule>,
+    /// browser profile resolved from `application` or `default`, eg: `Firefox:work`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub profile: Option<String>,
+    /// id of the desktop file resolved from `application` or `default`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub desktop_file_id: String,
+    /// indexes of this rule regexps in `Config::regex_set`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub regex_range: Range<usize>,
+    /// indexes of this rule exclude regexps in `Config::regex_set`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub exclude_regex_range: Range<usize>,
+    /// where this rule is defined, legacy rules come from `[[application]]`
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub source: Location,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RewriteConfig {
+    /// replaces known redirectors (Google, Outlook SafeLinks, Slack...) by their target
+    #[serde(default = "default_unwrap_redirects")]
+    pub unwrap_redirects: bool,
+    /// query parameters to remove, a trailing `*` matches any suffix
+    #[serde(default = "default_strip_params")]
+    pub strip_params: Vec<String>,
+    /// user defined substitutions, applied in order after the other steps
+    #[serde(rename = "substitution", default)]
+    pub substitutions: Vec<SubstitutionConfig>,
+}
+
+impl Default for RewriteConfig {
+    fn default() -> Self {
+        Self {
+            unwrap_redirects: default_unwrap_redirects(),
+            strip_params: default_strip_params(),
+            substitutions: Vec::new(),
+        }
+    }
+}
+
+fn default_unwrap_redirects() -> bool {
+    true
+}
+
+fn default_strip_params() -> Vec<String> {
+    ["utm_*", "fbclid", "gclid"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubstitutionConfig {
+    pub regexp: String,
+    /// `$1` or `${name}` are replaced by the captured groups
+    pub replace: String,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub compiled: Option<Regex>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
-    #[serde(rename = "application")]
+    #[serde(rename = "application", default)]
     pub desktop_files: Vec<DesktopFileConfig>,
+    /// rules are evaluated in file order, then the legacy `[[application]]` matchers
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<RuleConfig>,
+    /// applied to uris before matching them
+    #[serde(default)]
+    pub rewrite: RewriteConfig,
+    /// every rule regexps compiled at load time, so an uri is matched in a single pass
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub regex_set: RegexSet,
 }
 
 impl Config {
-    pub fn read() -> Result<Self> {
+    pub fn path() -> Result<PathBuf> {
         let xdg_dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
-        let config_path = xdg_dirs.place_config_file("config.toml")?;
+        Ok(xdg_dirs.place_config_file("config.toml")?)
+    }
+
+    pub fn read() -> Result<Self> {
+        let config_path = Self::path()?;
         info!("config path: {}", config_path.display());
 
-        let config_content = fs::read_to_string(&config_path)?;
-        let mut config: Config = toml::from_str(&config_content)?;
+        let config_content = match fs::read_to_string(&config_path) {
+            Ok(config_content) => config_content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("config file not found, generating one from installed browsers");
+                crate::init::init_config(&config_path)?;
+                fs::read_to_string(&config_path)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Self::parse(&config_content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let (config, errors) = Self::parse_unchecked(content)?;
+        if !errors.is_empty() {
+            return Err(format_err!(
+                "{}",
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Parses the config, collecting every validation error instead of stopping at the first one.
+    /// Only TOML syntax and type errors are returned as `Err`.
+    pub fn parse_unchecked(content: &str) -> Result<(Self, Vec<ConfigError>)> {
+        let mut config: Config = toml::from_str(content)?;
+        let errors = config.prepare();
+        Ok((config, errors))
+    }
 
-        for desktop_file in &mut config.desktop_files {
-            // TODO: might compiple regexps here
+    /// Resolves ids and rules, and compiles regexps.
+    fn prepare(&mut self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
 
-            desktop_file.id = desktop_file.path.clone();
+        for (index, desktop_file) in self.desktop_files.iter_mut().enumerate() {
+            let location = Location::Application(index);
+            desktop_file.id = match (&desktop_file.desktop_id, &desktop_file.path) {
+                (Some(desktop_id), None) if desktop_id.ends_with(".desktop") => desktop_id.clone(),
+                (Some(desktop_id), None) => format!("{desktop_id}.desktop"),
+                (None, Some(path)) => path.clone(),
+                (Some(_), Some(_)) => {
+                    errors.push(ConfigError::new(
+                        location,
+                        "application can not have both `desktop_id` and `path`",
+                    ));
+                    continue;
+                }
+                (None, None) => {
+                    errors.push(ConfigError::new(
+                        location,
+                        "application must have either `desktop_id` or `path`",
+                    ));
+                    continue;
+                }
+            };
         }
 
-        Ok(config)
+        for (index, desktop_file) in self.desktop_files.iter().enumerate() {
+            let previous = &self.desktop_files[..index];
+            if let Some(name) = &desktop_file.name
+                && previous.iter().any(|df| df.name.as_ref() == Some(name))
+            {
+                errors.push(ConfigError::new(
+                    Location::Application(index),
+                    format!("duplicate application name: {}", name),
+                ));
+            }
+            if !desktop_file.id.is_empty() && previous.iter().any(|df| df.id == desktop_file.id) {
+                errors.push(ConfigError::new(
+                    Location::Application(index),
+                    format!("duplicate application: {}", desktop_file.id),
+                ));
+            }
+            if let Some(key) = &desktop_file.key {
+                match desktop_file.key() {
+                    None => errors.push(ConfigError::new(
+                        Location::Application(index),
+                        format!("invalid key '{}', expected a letter from a to z", key),
+                    )),
+                    Some(key) if previous.iter().any(|df| df.key() == Some(key)) => {
+                        errors.push(ConfigError::new(
+                            Location::Application(index),
+                            format!("duplicate application key: {}", key),
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            // both would be `<application>:<variant>` choices
+            if desktop_file.expand_actions == Some(true) && desktop_file.profiles.is_some() {
+                errors.push(ConfigError::new(
+                    Location::Application(index),
+                    "application can not have both `expand_actions` and `profiles`",
+                ));
+            }
+        }
+
+        for (index, rule) in self.rules.iter_mut().enumerate() {
+            let location = Location::Rule(index);
+            rule.source = location;
+            let application = match (&rule.application, &rule.default) {
+                (Some(application), None) => {
+                    if rule.matchers.is_empty() {
+                        errors.push(ConfigError::new(
+                            location,
+                            format!("rule for '{}' has no matcher", application),
+                        ));
+                    }
+                    if rule.days.is_some() || rule.from.is_some() || rule.to.is_some() {
+                        errors.push(ConfigError::new(
+                            location,
+                            format!(
+                                "rule for '{}' can not have a schedule, only default rules can",
+                                application
+                            ),
+                        ));
+                    }
+                    application
+                }
+                (None, Some(application)) => {
+                    if !rule.matchers.is_empty() || rule.matchers.has_exclusions() {
+                        errors.push(ConfigError::new(
+                            location,
+                            format!("default rule for '{}' can not have matchers", application),
+                        ));
+                    }
+                    if rule.days.is_some() || rule.from.is_some() || rule.to.is_some() {
+                        match Schedule::parse(
+                            rule.days.as_deref().unwrap_or_default(),
+                            rule.from.as_deref(),
+                            rule.to.as_deref(),
+                        ) {
+                            Ok(schedule) => rule.schedule = Some(schedule),
+                            Err(e) => errors.push(ConfigError::new(
+                                location,
+                                format!("default rule for '{}': {}", application, e),
+                            )),
+                        }
+                    }
+                    application
+                }
+                _ => {
+                    errors.push(ConfigError::new(
+                        location,
+                        "rule must have either `application` or `default`",
+                    ));
+                    continue;
+                }
+            };
+            // `<application>:<variant>` targets a profile, or a desktop action, of the application
+            let (desktop_file, variant) = match find_application(&self.desktop_files, application) {
+                Some(desktop_file) => (Some(desktop_file), None),
+                None => match application.rsplit_once(':') {
+                    Some((reference, variant)) => (
+                        find_application(&self.desktop_files, reference),
+                        Some(variant),
+                    ),
+                    None => (None, None),
+                },
+            };
+            match (desktop_file, variant) {
+                (Some(desktop_file), Some(variant)) if desktop_file.profiles.is_some() => {
+                    rule.desktop_file_id = desktop_file.id.clone();
+                    rule.profile = Some(variant.to_string());
+                }
+                (Some(_), Some(_)) if rule.action.is_some() => errors.push(ConfigError::new(
+                    location,
+                    format!("rule for '{}' can not also have an `action`", application),
+                )),
+                (Some(desktop_file), action) => {
+                    rule.desktop_file_id = desktop_file.id.clone();
+                    if let Some(action) = action {
+                        rule.action = Some(action.to_string());
+                    }
+                }
+                (None, _) => errors.push(ConfigError::new(
+                    location,
+                    format!("unknown application '{}'", application),
+                )),
+            }
+        }
+
+        // the old syntax attaches matchers to applications, we keep it working
+        // by turning them into rules evaluated after the `[[rule]]` tables
+        let legacy_rules = self
+            .desktop_files
+            .iter()
+            .enumerate()
+            .filter(|(_, df)| !df.matchers.is_empty())
+            .map(|(index, df)| RuleConfig {
+                application: Some(
+                    df.name
+                        .clone()
+                        .or_else(|| df.alias.clone())
+                        .unwrap_or_else(|| df.id.clone()),
+                ),
+                default: None,
+                // the application options are used on launch anyway
+                args: None,
+                action: None,
+                days: None,
+                from: None,
+                to: None,
+                matchers: df.matchers.clone(),
+                schedule: None,
+                profile: None,
+                desktop_file_id: df.id.clone(),
+                regex_range: 0..0,
+                exclude_regex_range: 0..0,
+                source: Location::Application(index),
+            })
+            .collect::<Vec<_>>();
+        self.rules.extend(legacy_rules);
+
+        for rule in &self.rules {
+            let hosts = rule.matchers.hosts.iter().flatten();
+            let exclude_hosts = rule.matchers.exclude_hosts.iter().flatten();
+            for host in hosts.chain(exclude_hosts) {
+                if host.trim_start_matches("*.").contains('*') {
+                    errors.push(ConfigError::new(
+                        rule.source,
+                        format!("invalid host '{}', only a leading `*.` is supported", host),
+                    ));
+                }
+            }
+        }
+
+        // validating each pattern first to report which one is invalid
+        let mut patterns = Vec::new();
+        let mut compile = |rule: &RuleConfig, regexps: &Option<Vec<String>>| {
+            let start = patterns.len();
+            for regexp in regexps.iter().flatten() {
+                if let Err(e) = Regex::new(regexp) {
+                    errors.push(ConfigError::new(
+                        rule.source,
+                        format!(
+                            "invalid regexp for application '{}': {}: {}",
+                            rule.application_reference(),
+                            regexp,
+                            e
+                        ),
+                    ));
+                    continue;
+                }
+                patterns.push(regexp.clone());
+            }
+            start..patterns.len()
+        };
+        for rule in &mut self.rules {
+            rule.regex_range = compile(rule, &rule.matchers.regexps);
+            rule.exclude_regex_range = compile(rule, &rule.matchers.exclude_regexps);
+        }
+        match RegexSet::new(patterns) {
+            Ok(regex_set) => self.regex_set = regex_set,
+            Err(e) => errors.push(ConfigError::new(Location::Root, e.to_string())),
+        }
+
+        for (index, substitution) in self.rewrite.substitutions.iter_mut().enumerate() {
+            match Regex::new(&substitution.regexp) {
+                Ok(regex) => substitution.compiled = Some(regex),
+                Err(e) => errors.push(ConfigError::new(
+                    Location::Substitution(index),
+                    format!("invalid regexp: {}: {}", substitution.regexp, e),
+                )),
+            }
+        }
+
+        errors
+    }
+
+    /// Rules evaluated for an uri, in order. A default rule without schedule ends the evaluation.
+    pub fn matching_rules(&self) -> impl Iterator<Item = &RuleConfig> {
+        self.rules
+            .iter()
+            .take_while(|rule| !rule.is_terminal_default())
+            .filter(|rule| !rule.is_default())
     }
 
-    pub fn find_matching_desktop_file(&self, uri: &str) -> Option<&DesktopFileConfig> {
-        self.desktop_files.iter().find(|df| df.match_uri(uri))
+    /// First default rule whose schedule includes `now`.
+    pub fn active_default_rule(&self, now: NaiveDateTime) -> Option<&RuleConfig> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.is_default())
+            .find(|rule| {
+                rule.schedule
+                    .as_ref()
+                    .is_none_or(|schedule| schedule.is_active(now))
+            })
+    }
+
+    /// Evaluates the rules in order until one matches.
+    /// The last evaluation is the matching rule, if any.
+    pub fn evaluate(&self, uri: &str) -> Vec<Evaluation<'_>> {
+        let regex_matches = self.regex_set.matches(uri);
+        let url = Url::parse(uri).ok();
+        let mut evaluations = Vec::new();
+        for rule in self.matching_rules() {
+            let matched = self.match_rule(rule, uri, url.as_ref(), &regex_matches);
+            let excluded_by = matched
+                .as_ref()
+                .and_then(|_| self.match_exclusion(rule, uri, url.as_ref(), &regex_matches));
+            if let (Some(matched), Some(excluded_by)) = (&matched, &excluded_by) {
+                info!(
+                    "{} matched with {} but is excluded by {}",
+                    rule.source, matched, excluded_by
+                );
+            }
+            let evaluation = Evaluation {
+                rule,
+                matched,
+                excluded_by,
+            };
+            let is_match = evaluation.is_match();
+            evaluations.push(evaluation);
+            if is_match {
+                break;
+            }
+        }
+        evaluations
+    }
+
+    /// `runtime_default` is the default application id set on the daemon, if any,
+    /// `now` is used for the default rules schedules.
+    pub fn decide<'a>(
+        &'a self,
+        uri: &str,
+        runtime_default: Option<&'a str>,
+        now: NaiveDateTime,
+    ) -> Decision<'a> {
+        if let Some(evaluation) = self.evaluate(uri).pop()
+            && evaluation.is_match()
+            && let Some(matched) = evaluation.matched
+        {
+            return Decision::Rule(evaluation.rule, matched);
+        }
+        // `mailto:` and friends are not for browsers
+        if let Ok(url) = Url::parse(uri)
+            && !matches!(url.scheme(), "http" | "https" | "file")
+            && let Some(desktop_id) = scheme_handler(url.scheme())
+        {
+            return Decision::SchemeHandler {
+                desktop_id,
+                scheme: url.scheme().to_string(),
+            };
+        }
+        if let Some(choice_id) = runtime_default
+            && let Some((desktop_file, options)) = self.get_choice(choice_id)
+        {
+            return Decision::RuntimeDefault {
+                choice_id,
+                desktop_file,
+                options,
+            };
+        }
+        if let Some(rule) = self.active_default_rule(now) {
+            return Decision::DefaultRule(rule);
+        }
+        Decision::Ui
+    }
+
+    /// Decides for each uri and groups the ones launching the same application with the same options,
+    /// the uris left need the UI.
+    pub fn route(
+        &self,
+        uris: &[String],
+        runtime_default: Option<&str>,
+        now: NaiveDateTime,
+    ) -> (Vec<LaunchGroup>, Vec<String>) {
+        let mut groups: Vec<LaunchGroup> = Vec::new();
+        let mut pending = Vec::new();
+        for uri in uris {
+            let decision = self.decide(uri, runtime_default, now);
+            let Some(desktop_file_id) = decision.desktop_file_id() else {
+                pending.push(uri.clone());
+                continue;
+            };
+            info!("{}: {}", uri, decision);
+            let options = decision.launch_options(self);
+            match groups
+                .iter_mut()
+                .find(|group| group.desktop_file_id == desktop_file_id && group.options == options)
+            {
+                Some(group) => group.uris.push(uri.clone()),
+                None => groups.push(LaunchGroup {
+                    desktop_file_id: desktop_file_id.to_string(),
+                    options,
+                    uris: vec![uri.clone()],
+                }),
+            }
+        }
+        (groups, pending)
+    }
+
+    /// `url` is the parsed uri if valid, `regex_matches` are the matches of `regex_set` for this uri.
+    fn match_rule(
+        &self,
+        rule: &RuleConfig,
+        uri: &str,
+        url: Option<&Url>,
+        regex_matches: &SetMatches,
+    ) -> Option<MatchReason> {
+        // testing prefixes since it should be faster than regexps
+        if let Some(prefix) = rule
+            .matchers
+            .prefixes
+            .iter()
+            .flatten()
+            .find(|prefix| uri.starts_with(prefix.as_str()))
+        {
+            return Some(MatchReason::Prefix(prefix.clone()));
+        }
+        // and now regexps
+        if let Some(index) = rule
+            .regex_range
+            .clone()
+            .find(|index| regex_matches.matched(*index))
+        {
+            return Some(MatchReason::Regexp(
+                self.regex_set.patterns()[index].clone(),
+            ));
+        }
+        // and structured matchers
+        url.and_then(|url| rule.matchers.match_url(url))
+            .map(MatchReason::Url)
+    }
+
+    /// Same as `match_rule` with the exclusions, any of them is enough.
+    fn match_exclusion(
+        &self,
+        rule: &RuleConfig,
+        uri: &str,
+        url: Option<&Url>,
+        regex_matches: &SetMatches,
+    ) -> Option<MatchReason> {
+        if let Some(prefix) = rule
+            .matchers
+            .exclude_prefixes
+            .iter()
+            .flatten()
+            .find(|prefix| uri.starts_with(prefix.as_str()))
+        {
+            return Some(MatchReason::Prefix(prefix.clone()));
+        }
+        if let Some(index) = rule
+            .exclude_regex_range
+            .clone()
+            .find(|index| regex_matches.matched(*index))
+        {
+            return Some(MatchReason::Regexp(
+                self.regex_set.patterns()[index].clone(),
+            ));
+        }
+        url.and_then(|url| rule.matchers.match_url_exclusion(url))
+            .map(MatchReason::Url)
+    }
+
+    pub fn get_desktop_file(&self, id: &str) -> Option<&DesktopFileConfig> {
+        self.desktop_files.iter().find(|df| df.id == id)
+    }
+
+    /// [`Choice`] id of an application reference, written like in rules.
+    pub fn find_choice_id(&self, reference: &str) -> Option<String> {
+        if let Some(desktop_file) = find_application(&self.desktop_files, reference) {
+            return Some(desktop_file.id.clone());
+        }
+        let (reference, variant) = reference.rsplit_once(':')?;
+        let desktop_file = find_application(&self.desktop_files, reference)?;
+        Some(format!("{}:{}", desktop_file.id, variant))
+    }
+
+    /// Application and launch options of a [`Choice`] id.
+    pub fn get_choice(&self, choice_id: &str) -> Option<(&DesktopFileConfig, LaunchOptions)> {
+        if let Some(desktop_file) = self.get_desktop_file(choice_id) {
+            return Some((desktop_file, desktop_file.launch_options()));
+        }
+        let (id, variant) = choice_id.rsplit_once(':')?;
+        let desktop_file = self.get_desktop_file(id)?;
+        Some((desktop_file, desktop_file.variant_launch_options(variant)))
+    }
+}
+
+/// Uris opened in one launch.
+#[derive(Clone, Debug)]
+pub struct LaunchGroup {
+    pub desktop_file_id: String,
+    pub options: LaunchOptions,
+    pub uris: Vec<String>,
+}
+
+/// Something to pick in the UI or as the daemon default: an application,
+/// or one of its desktop actions or browser profiles when `expand_actions` or `profiles` is set.
+#[derive(Clone, Debug)]
+pub struct Choice {
+    /// the application id, or `<application id>:<action or profile>`
+    pub id: String,
+    pub desktop_file_id: String,
+    /// displayed name
+    pub name: String,
+    /// browser profile color, eg: `#1a73e8`
+    pub color: Option<String>,
+    /// keyboard shortcut of the application, not of its actions or profiles
+    pub key: Option<char>,
+    pub options: LaunchOptions,
+}
+
+/// How an application is launched.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LaunchOptions {
+    /// desktop action to launch instead of the main entry
+    pub action: Option<String>,
+    /// extra arguments, given before the uris
+    pub args: Vec<String>,
+}
+
+impl LaunchOptions {
+    pub fn is_empty(&self) -> bool {
+        self.action.is_none() && self.args.is_empty()
     }
 }
 
 impl DesktopFileConfig {
-    pub fn match_uri(&self, uri: &str) -> bool {
-        if self.prefixes.is_none() && self.regexps.is_none() {
-            return false;
+    /// The `key` letter, lowercased, if it is a valid one.
+    pub fn key(&self) -> Option<char> {
+        let mut chars = self.key.as_deref()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(key), None) if key.is_ascii_alphabetic() => Some(key.to_ascii_lowercase()),
+            _ => None,
         }
-        // testing prefixes since it should be faster than regexps
-        if let Some(prefixes) = &self.prefixes {
-            for prefix in prefixes {
-                if uri.starts_with(prefix) {
-                    return true;
-                }
+    }
+
+    pub fn launch_options(&self) -> LaunchOptions {
+        LaunchOptions {
+            action: self.action.clone(),
+            args: self.args.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Options to launch one of the desktop actions, the application ones are kept.
+    pub fn action_launch_options(&self, action: &str) -> LaunchOptions {
+        LaunchOptions {
+            action: Some(action.to_string()),
+            ..self.launch_options()
+        }
+    }
+
+    /// Options to launch one of the browser profiles, the application ones are kept.
+    pub fn profile_launch_options(&self, profile: &str) -> LaunchOptions {
+        let mut options = self.launch_options();
+        if let Some(kind) = self.profiles {
+            options.args.extend(kind.args(profile));
+        }
+        options
+    }
+
+    /// `variant` is a profile if `profiles` is set, a desktop action otherwise.
+    pub fn variant_launch_options(&self, variant: &str) -> LaunchOptions {
+        match self.profiles {
+            Some(_) => self.profile_launch_options(variant),
+            None => self.action_launch_options(variant),
+        }
+    }
+}
+
+impl RuleConfig {
+    /// Application options, extended by the rule ones.
+    pub fn launch_options(&self, desktop_file: Option<&DesktopFileConfig>) -> LaunchOptions {
+        let mut options = match (desktop_file, &self.profile) {
+            (Some(df), Some(profile)) => df.profile_launch_options(profile),
+            (Some(df), None) => df.launch_options(),
+            (None, _) => LaunchOptions::default(),
+        };
+        if let Some(action) = &self.action {
+            options.action = Some(action.clone());
+        }
+        options.args.extend(self.args.iter().flatten().cloned());
+        options
+    }
+}
+
+/// Result of a rule evaluation for an uri.
+pub struct Evaluation<'a> {
+    pub rule: &'a RuleConfig,
+    pub matched: Option<MatchReason>,
+    /// exclusions are only checked if the rule matched
+    pub excluded_by: Option<MatchReason>,
+}
+
+impl Evaluation<'_> {
+    pub fn is_match(&self) -> bool {
+        self.matched.is_some() && self.excluded_by.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum MatchReason {
+    Prefix(String),
+    Regexp(String),
+    /// structured matchers on the parsed uri
+    Url(String),
+}
+
+impl std::fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchReason::Prefix(prefix) => write!(f, "prefix '{}'", prefix),
+            MatchReason::Regexp(regexp) => write!(f, "regexp '{}'", regexp),
+            MatchReason::Url(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// What to do with an uri.
+pub enum Decision<'a> {
+    /// A rule matched.
+    Rule(&'a RuleConfig, MatchReason),
+    /// Nothing matched and the uri is not a web one, the system handler of its scheme is used.
+    SchemeHandler { desktop_id: String, scheme: String },
+    /// Nothing matched, the default choice set on the daemon is used.
+    RuntimeDefault {
+        choice_id: &'a str,
+        desktop_file: &'a DesktopFileConfig,
+        options: LaunchOptions,
+    },
+    /// Nothing matched, the `default` rule is used.
+    DefaultRule(&'a RuleConfig),
+    /// Nothing matched, the user has to choose.
+    Ui,
+}
+
+impl Decision<'_> {
+    /// Desktop file to launch, `None` means the UI.
+    pub fn desktop_file_id(&self) -> Option<&str> {
+        match self {
+            Decision::Rule(rule, _) | Decision::DefaultRule(rule) => Some(&rule.desktop_file_id),
+            Decision::SchemeHandler { desktop_id, .. } => Some(desktop_id),
+            Decision::RuntimeDefault { desktop_file, .. } => Some(&desktop_file.id),
+            Decision::Ui => None,
+        }
+    }
+
+    pub fn launch_options(&self, cfg: &Config) -> LaunchOptions {
+        match self {
+            Decision::Rule(rule, _) | Decision::DefaultRule(rule) => {
+                rule.launch_options(cfg.get_desktop_file(&rule.desktop_file_id))
             }
+            Decision::RuntimeDefault { options, .. } => options.clone(),
+            Decision::SchemeHandler { .. } | Decision::Ui => LaunchOptions::default(),
         }
-        // and now regexps
-        if let Some(regexps) = &self.regexps {
-            for regexp in regexps {
-                // TODO: maybe cache regexps later
-                if Regex::new(regexp).map(|r| r.is_match(uri)).unwrap_or(false) {
-                    return true;
+    }
+}
+
+impl std::fmt::Display for Decision<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Decision::Rule(rule, reason) => write!(
+                f,
+                "launch '{}', {} matched with {}",
+                rule.application_reference(),
+                rule.source,
+                reason
+            ),
+            Decision::SchemeHandler { desktop_id, scheme } => {
+                write!(
+                    f,
+                    "launch '{}', default handler for {}:",
+                    desktop_id, scheme
+                )
+            }
+            Decision::RuntimeDefault { choice_id, .. } => {
+                write!(f, "launch '{}', daemon default application", choice_id)
+            }
+            Decision::DefaultRule(rule) => {
+                write!(
+                    f,
+                    "launch '{}', {} is the default rule",
+                    rule.application_reference(),
+                    rule.source
+                )?;
+                if let Some(schedule) = &rule.schedule {
+                    write!(f, " ({})", schedule)?;
                 }
+                Ok(())
+            }
+            Decision::Ui => write!(f, "fall back to UI"),
+        }
+    }
+}
+
+/// Rules reference applications by `name`, then by `alias`, then by id.
+pub fn find_application<'a>(
+    desktop_files: &'a [DesktopFileConfig],
+    reference: &str,
+) -> Option<&'a DesktopFileConfig> {
+    desktop_files
+        .iter()
+        .find(|df| df.name.as_deref() == Some(reference))
+        .or_else(|| {
+            desktop_files
+                .iter()
+                .find(|df| df.alias.as_deref() == Some(reference))
+        })
+        .or_else(|| desktop_files.iter().find(|df| df.id == reference))
+}
+
+impl RuleConfig {
+    pub fn is_default(&self) -> bool {
+        self.default.is_some()
+    }
+
+    /// A default rule without schedule always applies, the rules after it are never evaluated.
+    pub fn is_terminal_default(&self) -> bool {
+        self.is_default() && self.schedule.is_none()
+    }
+
+    /// [`Choice`] id launched by this rule.
+    pub fn choice_id(&self) -> String {
+        match (&self.profile, &self.action) {
+            (Some(variant), _) | (None, Some(variant)) => {
+                format!("{}:{}", self.desktop_file_id, variant)
             }
+            (None, None) => self.desktop_file_id.clone(),
+        }
+    }
+
+    /// Name of the application as written in the config.
+    pub fn application_reference(&self) -> &str {
+        self.application
+            .as_deref()
+            .or(self.default.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+impl Matchers {
+    pub fn is_empty(&self) -> bool {
+        is_empty(&self.prefixes) && is_empty(&self.regexps) && !self.has_url_matchers()
+    }
+
+    pub fn has_url_matchers(&self) -> bool {
+        !is_empty(&self.hosts)
+            || !is_empty(&self.schemes)
+            || !is_empty(&self.path_prefixes)
+            || !is_empty(&self.queries)
+            || !is_empty(&self.mime_types)
+    }
+
+    pub fn has_exclusions(&self) -> bool {
+        !is_empty(&self.exclude_prefixes)
+            || !is_empty(&self.exclude_regexps)
+            || !is_empty(&self.exclude_hosts)
+            || !is_empty(&self.exclude_path_prefixes)
+    }
+
+    /// Unlike positive matchers, any structured exclusion is enough.
+    pub fn match_url_exclusion(&self, url: &Url) -> Option<String> {
+        if let Some(host) = url.host_str()
+            && let Some(pattern) = self
+                .exclude_hosts
+                .iter()
+                .flatten()
+                .find(|pattern| match_host(pattern, host))
+        {
+            return Some(format!("host '{}'", pattern));
+        }
+        self.exclude_path_prefixes
+            .iter()
+            .flatten()
+            .find(|prefix| url.path().starts_with(prefix.as_str()))
+            .map(|prefix| format!("path prefix '{}'", prefix))
+    }
+
+    /// Every structured matcher set has to match, returns what matched.
+    pub fn match_url(&self, url: &Url) -> Option<String> {
+        if !self.has_url_matchers() {
+            return None;
+        }
+        let mut reasons = Vec::new();
+
+        if let Some(hosts) = self.hosts.as_ref().filter(|h| !h.is_empty()) {
+            let host = url.host_str()?;
+            let pattern = hosts.iter().find(|pattern| match_host(pattern, host))?;
+            reasons.push(format!("host '{}'", pattern));
+        }
+        if let Some(schemes) = self.schemes.as_ref().filter(|s| !s.is_empty()) {
+            let scheme = schemes
+                .iter()
+                .find(|scheme| scheme.eq_ignore_ascii_case(url.scheme()))?;
+            reasons.push(format!("scheme '{}'", scheme));
+        }
+        if let Some(path_prefixes) = self.path_prefixes.as_ref().filter(|p| !p.is_empty()) {
+            let path_prefix = path_prefixes
+                .iter()
+                .find(|prefix| url.path().starts_with(prefix.as_str()))?;
+            reasons.push(format!("path prefix '{}'", path_prefix));
+        }
+        if let Some(queries) = self.queries.as_ref().filter(|q| !q.is_empty()) {
+            let query = queries.iter().find(|query| match_query(query, url))?;
+            reasons.push(format!("query '{}'", query));
+        }
+        if let Some(mime_types) = self.mime_types.as_ref().filter(|m| !m.is_empty()) {
+            let path = url.to_file_path().ok()?;
+            let mime_type = guess_mime_type(&path)?;
+            let pattern = mime_types
+                .iter()
+                .find(|pattern| match_mime_type(pattern, &mime_type))?;
+            reasons.push(format!("mime type '{}'", pattern));
         }
-        false
+
+        Some(reasons.join(" and "))
+    }
+}
+
+fn is_empty(values: &Option<Vec<String>>) -> bool {
+    values.as_ref().is_none_or(|v| v.is_empty())
+}
+
+/// `*.example.com` matches `example.com` and all its subdomains.
+fn match_host(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => {
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        }
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// `image/*` matches every image type.
+fn match_mime_type(pattern: &str, mime_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(media_type) => mime_type
+            .split_once('/')
+            .is_some_and(|(t, _)| t.eq_ignore_ascii_case(media_type)),
+        None => mime_type.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// `key` matches if the parameter is present, `key=value` if it has this value.
+fn match_query(pattern: &str, url: &Url) -> bool {
+    let (key, value) = match pattern.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (pattern, None),
+    };
+    url.query_pairs()
+        .any(|(k, v)| k == key && value.is_none_or(|value| v == value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::at;
+
+    const APPLICATIONS: &str = r#"
+[[application]]
+desktop_id = "firefox.desktop"
+name = "Perso"
+
+[[application]]
+path = "/opt/firefox-work.desktop"
+name = "Work"
+"#;
+
+    const DEFAULT_RULES: &str = r#"
+[[rule]]
+hosts = ["work.example.com"]
+application = "Work"
+
+[[rule]]
+default = "Work"
+days = ["weekdays"]
+from = "09:00"
+to = "18:00"
+
+[[rule]]
+default = "Perso"
+"#;
+
+    fn config(rules: &str) -> Config {
+        Config::parse(&format!("{}{}", rules, APPLICATIONS)).unwrap()
+    }
+
+    /// Application of the rule matching `uri`, if any.
+    fn matched_application(cfg: &Config, uri: &str) -> Option<String> {
+        cfg.evaluate(uri)
+            .pop()
+            .filter(|evaluation| evaluation.is_match())
+            .map(|evaluation| evaluation.rule.application_reference().to_string())
+    }
+
+    #[test]
+    fn match_host() {
+        let cfg = config(
+            r#"
+[[rule]]
+hosts = ["*.example.com", "github.com"]
+application = "Work"
+"#,
+        );
+        let matched = |uri| matched_application(&cfg, uri);
+        assert_eq!(matched("https://github.com/").as_deref(), Some("Work"));
+        assert_eq!(matched("https://GitHub.com/").as_deref(), Some("Work"));
+        assert_eq!(matched("https://gist.github.com/"), None);
+        // `*.` also matches the domain itself
+        assert_eq!(matched("https://example.com/").as_deref(), Some("Work"));
+        assert_eq!(
+            matched("https://docs.example.com/").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(matched("https://a.b.example.com/").as_deref(), Some("Work"));
+        assert_eq!(matched("https://notexample.com/"), None);
+    }
+
+    #[test]
+    fn match_query() {
+        let cfg = config(
+            r#"
+[[rule]]
+queries = ["tab=issues", "debug"]
+application = "Work"
+"#,
+        );
+        let matched = |uri| matched_application(&cfg, uri);
+        assert_eq!(
+            matched("https://example.com/?tab=issues").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(
+            matched("https://example.com/?a=1&tab=issues").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(matched("https://example.com/?tab=pulls"), None);
+        assert_eq!(matched("https://example.com/?tabs=issues"), None);
+        assert_eq!(
+            matched("https://example.com/?debug").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(
+            matched("https://example.com/?debug=1").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(matched("https://example.com/"), None);
+    }
+
+    #[test]
+    fn match_url_needs_every_kind() {
+        let cfg = config(
+            r#"
+[[rule]]
+schemes = ["https"]
+hosts = ["github.com", "gitlab.com"]
+path_prefixes = ["/fabienjuif/"]
+application = "Work"
+"#,
+        );
+        let matched = |uri| matched_application(&cfg, uri);
+        // any value of a kind is enough
+        assert_eq!(
+            matched("https://github.com/fabienjuif/choosme").as_deref(),
+            Some("Work")
+        );
+        assert_eq!(
+            matched("https://gitlab.com/fabienjuif/").as_deref(),
+            Some("Work")
+        );
+        // but every kind has to match
+        assert_eq!(matched("http://github.com/fabienjuif/choosme"), None);
+        assert_eq!(matched("https://example.com/fabienjuif/"), None);
+        assert_eq!(matched("https://github.com/someone/"), None);
+
+        let evaluation = cfg
+            .evaluate("https://github.com/fabienjuif/")
+            .pop()
+            .unwrap();
+        assert_eq!(
+            evaluation.matched.unwrap().to_string(),
+            "host 'github.com' and scheme 'https' and path prefix '/fabienjuif/'"
+        );
+    }
+
+    #[test]
+    fn prefixes_and_regexps_or_structured_matchers() {
+        let cfg = config(
+            r#"
+[[rule]]
+prefixes = ["https://intranet/"]
+regexps = ['^https://[^/]*\.corp/']
+hosts = ["example.com"]
+path_prefixes = ["/work/"]
+application = "Work"
+
+[[rule]]
+hosts = ["*.example.com"]
+application = "Perso"
+"#,
+        );
+        let matched = |uri| matched_application(&cfg, uri);
+        let reason = |uri| {
+            cfg.evaluate(uri)
+                .pop()
+                .and_then(|evaluation| evaluation.matched)
+        };
+
+        assert_eq!(matched("https://intranet/home").as_deref(), Some("Work"));
+        assert!(matches!(
+            reason("https://intranet/home"),
+            Some(MatchReason::Prefix(_))
+        ));
+        assert_eq!(matched("https://wiki.corp/").as_deref(), Some("Work"));
+        assert!(matches!(
+            reason("https://wiki.corp/"),
+            Some(MatchReason::Regexp(_))
+        ));
+        assert_eq!(
+            matched("https://example.com/work/").as_deref(),
+            Some("Work")
+        );
+        assert!(matches!(
+            reason("https://example.com/work/"),
+            Some(MatchReason::Url(_))
+        ));
+
+        // rules are evaluated in order until one matches
+        assert_eq!(
+            matched("https://example.com/home").as_deref(),
+            Some("Perso")
+        );
+        assert_eq!(cfg.evaluate("https://example.com/home").len(), 2);
+        assert_eq!(matched("https://other.com/"), None);
+    }
+
+    #[test]
+    fn exclusions_fall_through_to_the_next_rule() {
+        let cfg = config(
+            r#"
+[[rule]]
+hosts = ["*.example.com"]
+exclude_hosts = ["perso.example.com"]
+exclude_path_prefixes = ["/public/"]
+application = "Work"
+
+[[rule]]
+hosts = ["*.example.com"]
+application = "Perso"
+"#,
+        );
+        let matched = |uri| matched_application(&cfg, uri);
+        assert_eq!(matched("https://www.example.com/").as_deref(), Some("Work"));
+        assert_eq!(
+            matched("https://www.example.com/public/a").as_deref(),
+            Some("Perso")
+        );
+
+        let evaluations = cfg.evaluate("https://perso.example.com/");
+        assert_eq!(evaluations.len(), 2);
+        assert!(evaluations[0].matched.is_some());
+        assert_eq!(
+            evaluations[0]
+                .excluded_by
+                .as_ref()
+                .map(|reason| reason.to_string())
+                .as_deref(),
+            Some("host 'perso.example.com'")
+        );
+        assert!(!evaluations[0].is_match());
+        assert!(evaluations[1].is_match());
+        assert_eq!(evaluations[1].rule.application_reference(), "Perso");
+    }
+
+    #[test]
+    fn match_exclusion() {
+        let cfg = config(
+            r#"
+[[rule]]
+prefixes = ["https://example.com/"]
+exclude_prefixes = ["https://example.com/login"]
+exclude_regexps = ['[?&]private\b']
+application = "Work"
+
+[[rule]]
+default = "Perso"
+"#,
+        );
+        let excluded_by = |uri| {
+            cfg.evaluate(uri)
+                .pop()
+                .and_then(|evaluation| evaluation.excluded_by)
+        };
+        assert!(matches!(
+            excluded_by("https://example.com/login?next=/"),
+            Some(MatchReason::Prefix(_))
+        ));
+        assert!(matches!(
+            excluded_by("https://example.com/?a=1&private"),
+            Some(MatchReason::Regexp(_))
+        ));
+        assert!(excluded_by("https://example.com/home").is_none());
+        // exclusions are only checked once the rule matched
+        assert!(excluded_by("https://other.com/login?private").is_none());
+
+        // nothing else matches, the default rule applies
+        let decision = cfg.decide("https://example.com/login", None, at(2, 10, 0));
+        assert!(matches!(decision, Decision::DefaultRule(_)));
+        assert_eq!(decision.desktop_file_id(), Some("firefox.desktop"));
+    }
+
+    #[test]
+    fn scheduled_default_rule_comes_first() {
+        let cfg = config(DEFAULT_RULES);
+        let active = |now: NaiveDateTime| {
+            cfg.active_default_rule(now)
+                .and_then(|rule| rule.default.clone())
+        };
+        assert_eq!(active(at(2, 10, 0)).as_deref(), Some("Work"));
+        assert_eq!(active(at(2, 19, 0)).as_deref(), Some("Perso"));
+        assert_eq!(active(at(7, 10, 0)).as_deref(), Some("Perso"));
+    }
+
+    #[test]
+    fn decide_with_scheduled_default_rule() {
+        let cfg = config(DEFAULT_RULES);
+        let uri = "https://example.com/";
+
+        let decision = cfg.decide(uri, None, at(2, 10, 0));
+        assert!(matches!(decision, Decision::DefaultRule(_)));
+        assert_eq!(
+            decision.desktop_file_id(),
+            Some("/opt/firefox-work.desktop")
+        );
+
+        let decision = cfg.decide(uri, None, at(7, 10, 0));
+        assert!(matches!(decision, Decision::DefaultRule(_)));
+        assert_eq!(decision.desktop_file_id(), Some("firefox.desktop"));
+
+        // a matching rule and the daemon default come before the default rules
+        let decision = cfg.decide("https://work.example.com/", None, at(7, 10, 0));
+        assert!(matches!(decision, Decision::Rule(..)));
+        let decision = cfg.decide(uri, Some("firefox.desktop"), at(2, 10, 0));
+        assert!(matches!(decision, Decision::RuntimeDefault { .. }));
+        assert_eq!(decision.desktop_file_id(), Some("firefox.desktop"));
     }
 }
@@ -1,88 +1,136 @@
 use std::{
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{self, Receiver, Sender},
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use dbus::{MethodErr, blocking::Connection, channel::MatchingReceiver};
 use dbus_crossroads::{Context, Crossroads};
 use gtk4::gio::prelude::{AppInfoExt, IconExt};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
-    config::Config,
+    config::{Config, LaunchGroup},
     dbus::StatusCmdOutputApplication,
-    desktop_files::{DesktopFileOpenerCommand, resolve_desktop_files},
+    desktop_files::{DesktopFileOpenerCommand, resolve_choices, resolve_desktop_files},
+    rewrite::rewrite_uri,
+    schedule::{Clock, SystemClock},
+    state::State,
+    ui::UiCommand,
 };
 
+/// How long `open` waits for the desktop file opener to launch the applications,
+/// all of them together, it has to be shorter than the client D-Bus timeout.
+const LAUNCH_TIMEOUT: Duration = Duration::from_millis(1500);
+
 struct Daemon {
     cfg: Config,
-    default_application_id: Option<String>,
+    /// persisted default application and other runtime state
+    state: State,
+    /// evaluates the default rules schedules
+    clock: Box<dyn Clock + Send>,
     desktop_files_tx: Sender<DesktopFileOpenerCommand>,
-    toggle_ui_tx: async_channel::Sender<String>,
+    toggle_ui_tx: async_channel::Sender<UiCommand>,
 }
 
 impl Daemon {
     fn open(&self, inputs: crate::dbus::OpenCmdInputs) -> Result<crate::dbus::OpenCmdOutputs> {
         debug!("open command received with inputs: {:?}", inputs);
+        let outputs = self.open_uris(vec![inputs.uri])?;
+        if !outputs.failed.is_empty() {
+            return Err(anyhow::anyhow!("failed to launch: {:?}", outputs.failed));
+        }
+        Ok(crate::dbus::OpenCmdOutputs {
+            status: outputs.status,
+        })
+    }
 
-        // try to find a matching desktop file
-        if let Some(desktop_file) = self.cfg.find_matching_desktop_file(&inputs.uri) {
-            info!("found matching desktop file: {:?}", desktop_file.id);
-
-            // send command to desktop file opener
-            self.desktop_files_tx
-                .send(DesktopFileOpenerCommand::Open(
-                    crate::desktop_files::OpenParams {
-                        uris: vec![inputs.uri],
-                        desktop_file_id: desktop_file.id.clone(),
-                    },
-                ))
-                .map_err(|e| anyhow::anyhow!("failed to send command: {}", e))?;
-
-            return Ok(crate::dbus::OpenCmdOutputs {
-                status: crate::dbus::OpenCmdOutputsStatus::Launched,
-            });
+    fn open_many(
+        &self,
+        inputs: crate::dbus::OpenManyCmdInputs,
+    ) -> Result<crate::dbus::OpenManyCmdOutputs> {
+        debug!("open_many command received with inputs: {:?}", inputs);
+        self.open_uris(inputs.uris)
+    }
+
+    /// Each uri is routed on its own, the ones needing a choice share one UI session.
+    /// A failed launch does not stop the others, its uris are returned so only they are retried.
+    fn open_uris(&self, uris: Vec<String>) -> Result<crate::dbus::OpenManyCmdOutputs> {
+        if uris.is_empty() {
+            return Err(anyhow::anyhow!("no uri to open"));
         }
 
-        // fallback to default application if set
-        if let Some(default_id) = &self.default_application_id {
-            if let Some(desktop_file) = self
-                .cfg
-                .desktop_files
-                .iter()
-                .find(|df| &df.id == default_id)
-            {
-                info!("using default application: {:?}", desktop_file.id);
-
-                // send command to desktop file opener
-                self.desktop_files_tx
-                    .send(DesktopFileOpenerCommand::Open(
-                        crate::desktop_files::OpenParams {
-                            uris: vec![inputs.uri],
-                            desktop_file_id: desktop_file.id.clone(),
-                        },
-                    ))
-                    .map_err(|e| anyhow::anyhow!("failed to send command: {}", e))?;
-
-                return Ok(crate::dbus::OpenCmdOutputs {
-                    status: crate::dbus::OpenCmdOutputsStatus::Launched,
-                });
-            }
+        // the rewritten uris are the ones matched and launched
+        let uris = uris
+            .iter()
+            .map(|uri| rewrite_uri(&self.cfg.rewrite, uri))
+            .collect::<Vec<_>>();
+        let (groups, pending) = self.cfg.route(
+            &uris,
+            self.state.default_application_id.as_deref(),
+            self.clock.now(),
+        );
+        let failed = self.launch(groups);
+        if pending.is_empty() {
+            return Ok(crate::dbus::OpenManyCmdOutputs {
+                status: crate::dbus::OpenCmdOutputsStatus::Launched,
+                failed,
+            });
         }
 
         // fallbacking to UI
-        info!("no matching desktop file found, falling back to UI");
+        info!(
+            "no matching desktop file found for {} uri(s), falling back to UI",
+            pending.len()
+        );
         self.toggle_ui_tx
-            .send_blocking(inputs.uri)
+            .send_blocking(UiCommand::Show(pending))
             .map_err(|e| anyhow::anyhow!("failed to send toggle UI command: {}", e))?;
 
-        Ok(crate::dbus::OpenCmdOutputs {
+        Ok(crate::dbus::OpenManyCmdOutputs {
             status: crate::dbus::OpenCmdOutputsStatus::Fallbacked,
+            failed,
         })
     }
 
+    /// Sends every group to the desktop file opener, then waits for their results
+    /// so the caller can fall back on error. Returns the uris that failed to launch.
+    fn launch(&self, groups: Vec<LaunchGroup>) -> Vec<String> {
+        let deadline = Instant::now() + LAUNCH_TIMEOUT;
+        let mut failed = Vec::new();
+        let mut replies = Vec::new();
+        for group in groups {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            let uris = group.uris.clone();
+            if let Err(e) = self.desktop_files_tx.send(DesktopFileOpenerCommand::Open(
+                crate::desktop_files::OpenParams {
+                    uris: group.uris,
+                    desktop_file_id: group.desktop_file_id,
+                    options: group.options,
+                    reply: Some(reply_tx),
+                },
+            )) {
+                warn!("failed to send command: {}", e);
+                failed.extend(uris);
+                continue;
+            }
+            replies.push((uris, reply_rx));
+        }
+        for (uris, reply_rx) in replies {
+            match reply_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    warn!("failed to launch {:?}: {}", uris, e);
+                    failed.extend(uris);
+                }
+                // it may still be launched, retrying it could open the uris twice
+                Err(e) => warn!("no launch result for {:?}: {}", uris, e),
+            }
+        }
+        failed
+    }
+
     fn status(
         &self,
         inputs: crate::dbus::StatusCmdInputs,
@@ -90,23 +138,26 @@ impl Daemon {
         debug!("status command received with inputs: {:?}", inputs);
 
         let resolved = resolve_desktop_files(&self.cfg);
+        let default_rule_choice_id = self
+            .cfg
+            .active_default_rule(self.clock.now())
+            .map(|rule| rule.choice_id());
 
         Ok(crate::dbus::StatusCmdOutputs {
-            applications: self
-                .cfg
-                .desktop_files
-                .iter()
-                .map(|df| StatusCmdOutputApplication {
-                    id: df.id.clone(),
-                    name: df.alias.clone().unwrap_or_else(|| df.id.clone()),
-                    is_default: self.default_application_id.as_ref() == Some(&df.id),
+            applications: resolve_choices(&self.cfg, &resolved)
+                .into_iter()
+                .map(|choice| StatusCmdOutputApplication {
+                    is_default: self.state.default_application_id.as_ref() == Some(&choice.id),
+                    is_default_rule: default_rule_choice_id.as_ref() == Some(&choice.id),
                     icon: resolved
-                        .get(&df.id)
+                        .get(&choice.desktop_file_id)
                         .and_then(|d| {
                             d.icon()
                                 .map(|i| i.to_string().map_or("".to_string(), |i| i.into()))
                         })
                         .unwrap_or("".to_string()),
+                    id: choice.id,
+                    name: choice.name,
                 })
                 .collect(),
         })
@@ -124,6 +175,29 @@ impl Daemon {
         Ok(crate::dbus::KillCmdOutputs {})
     }
 
+    fn reload(
+        &mut self,
+        inputs: crate::dbus::ReloadCmdInputs,
+    ) -> Result<crate::dbus::ReloadCmdOutputs> {
+        debug!("reload command received with inputs: {:?}", inputs);
+
+        // on error we keep the current config
+        let cfg = Config::read()?;
+
+        self.desktop_files_tx
+            .send(DesktopFileOpenerCommand::Reload(cfg.clone()))
+            .map_err(|e| anyhow::anyhow!("failed to send reload command: {}", e))?;
+        self.toggle_ui_tx
+            .send_blocking(UiCommand::Reload(cfg.clone()))
+            .map_err(|e| anyhow::anyhow!("failed to send reload UI command: {}", e))?;
+
+        self.cfg = cfg;
+        self.drop_stale_default();
+        info!("config reloaded");
+
+        Ok(crate::dbus::ReloadCmdOutputs {})
+    }
+
     fn set_default(
         &mut self,
         inputs: crate::dbus::SetDefaultCmdInputs,
@@ -131,39 +205,113 @@ impl Daemon {
         debug!("set_default command received with inputs: {:?}", inputs);
 
         if inputs.index < 0 {
-            self.default_application_id = None;
+            self.set_default_application(None);
             return Ok(crate::dbus::SetDefaultCmdOutputs {});
         }
 
-        let desktop_file = self
-            .cfg
-            .desktop_files
-            .get(inputs.index as usize)
+        // indexes are the ones listed by `status`
+        let choice = resolve_choices(&self.cfg, &resolve_desktop_files(&self.cfg))
+            .into_iter()
+            .nth(inputs.index as usize)
             .ok_or_else(|| anyhow::anyhow!("invalid index: {}", inputs.index))?;
 
-        self.default_application_id = Some(desktop_file.id.clone());
+        self.set_default_application(Some(choice.id));
 
         Ok(crate::dbus::SetDefaultCmdOutputs {})
     }
+
+    fn set_default_by_id(
+        &mut self,
+        inputs: crate::dbus::SetDefaultByIdCmdInputs,
+    ) -> Result<crate::dbus::SetDefaultByIdCmdOutputs> {
+        debug!(
+            "set_default_by_id command received with inputs: {:?}",
+            inputs
+        );
+
+        // rules references (name, alias, id, `<application>:<variant>`) and status names
+        let choice_id = self.cfg.find_choice_id(&inputs.id);
+        let choice = resolve_choices(&self.cfg, &resolve_desktop_files(&self.cfg))
+            .into_iter()
+            .find(|choice| Some(&choice.id) == choice_id.as_ref() || choice.name == inputs.id)
+            .ok_or_else(|| anyhow::anyhow!("unknown application: {}", inputs.id))?;
+
+        self.set_default_application(Some(choice.id));
+
+        Ok(crate::dbus::SetDefaultByIdCmdOutputs {})
+    }
+
+    fn cycle_default(
+        &mut self,
+        inputs: crate::dbus::CycleDefaultCmdInputs,
+    ) -> Result<crate::dbus::CycleDefaultCmdOutputs> {
+        debug!("cycle_default command received with inputs: {:?}", inputs);
+
+        let choices = resolve_choices(&self.cfg, &resolve_desktop_files(&self.cfg));
+        // no default is one more step of the cycle, after the last choice
+        let steps = choices.len() + 1;
+        let current = self
+            .state
+            .default_application_id
+            .as_ref()
+            .and_then(|id| choices.iter().position(|choice| &choice.id == id))
+            .unwrap_or(choices.len());
+        let next = if inputs.forward {
+            (current + 1) % steps
+        } else {
+            (current + steps - 1) % steps
+        };
+
+        self.set_default_application(choices.into_iter().nth(next).map(|choice| choice.id));
+
+        Ok(crate::dbus::CycleDefaultCmdOutputs {})
+    }
+
+    /// Sets the default application and persists it.
+    fn set_default_application(&mut self, choice_id: Option<String>) {
+        // the UI writes the last used application in the same file
+        self.state = State::read();
+        self.state.default_application_id = choice_id;
+        if let Err(e) = self.state.write() {
+            warn!("failed to write state: {}", e);
+        }
+    }
+
+    /// The default application may have been removed from the config,
+    /// or be a profile or an action that is not offered anymore.
+    fn drop_stale_default(&mut self) {
+        let choices = resolve_choices(&self.cfg, &resolve_desktop_files(&self.cfg));
+        if let Some(default_id) = &self.state.default_application_id
+            && !choices.iter().any(|choice| &choice.id == default_id)
+        {
+            warn!(
+                "default application is not in the config anymore: {}",
+                default_id
+            );
+            self.set_default_application(None);
+        }
+    }
 }
 
 pub fn register_dbus(
     application_name: &str,
     cfg: Config,
     desktop_files_tx: Sender<DesktopFileOpenerCommand>,
-    toggle_ui_tx: async_channel::Sender<String>,
+    toggle_ui_tx: async_channel::Sender<UiCommand>,
     shutdown_rx: Receiver<()>,
 ) -> Result<JoinHandle<()>> {
     debug!("registering dbus for application: {}", application_name);
 
     // preparing daemon (thread safe is necessary for dbus)
     // TODO:
-    let daemon = Daemon {
+    let mut daemon = Daemon {
         cfg,
-        default_application_id: None,
+        state: State::read(),
+        clock: Box::new(SystemClock),
         desktop_files_tx,
         toggle_ui_tx,
     };
+    daemon.drop_stale_default();
 
     // dbus descriptions
     let c = Connection::new_session()?;
@@ -184,6 +332,20 @@ pub fn register_dbus(
             },
         );
 
+        b.method(
+            crate::dbus::OPEN_MANY_METHOD,
+            crate::dbus::OPEN_MANY_METHOD_INPUTS,
+            crate::dbus::OPEN_MANY_METHOD_OUTPUTS,
+            move |_: &mut Context, daemon: &mut Daemon, params: (Vec<String>,)| {
+                let inputs = crate::dbus::OpenManyCmdInputs::from_dbus_input(params);
+                let output = daemon
+                    .open_many(inputs)
+                    .map_err(|e| MethodErr::failed(&e.to_string()))?
+                    .to_dbus_output();
+                Ok(output)
+            },
+        );
+
         b.method(
             crate::dbus::STATUS_METHOD,
             crate::dbus::STATUS_METHOD_INPUTS,
@@ -225,6 +387,48 @@ pub fn register_dbus(
                 Ok(())
             },
         );
+
+        b.method(
+            crate::dbus::SET_DEFAULT_BY_ID_METHOD,
+            crate::dbus::SET_DEFAULT_BY_ID_METHOD_INPUTS,
+            crate::dbus::SET_DEFAULT_BY_ID_METHOD_OUTPUTS,
+            move |_: &mut Context, daemon: &mut Daemon, params: (String,)| {
+                let inputs = crate::dbus::SetDefaultByIdCmdInputs::from_dbus_input(params);
+                daemon
+                    .set_default_by_id(inputs)
+                    .map_err(|e| MethodErr::failed(&e.to_string()))?
+                    .to_dbus_output();
+                Ok(())
+            },
+        );
+
+        b.method(
+            crate::dbus::CYCLE_DEFAULT_METHOD,
+            crate::dbus::CYCLE_DEFAULT_METHOD_INPUTS,
+            crate::dbus::CYCLE_DEFAULT_METHOD_OUTPUTS,
+            move |_: &mut Context, daemon: &mut Daemon, params: (bool,)| {
+                let inputs = crate::dbus::CycleDefaultCmdInputs::from_dbus_input(params);
+                daemon
+                    .cycle_default(inputs)
+                    .map_err(|e| MethodErr::failed(&e.to_string()))?
+                    .to_dbus_output();
+                Ok(())
+            },
+        );
+
+        b.method(
+            crate::dbus::RELOAD_METHOD,
+            crate::dbus::RELOAD_METHOD_INPUTS,
+            crate::dbus::RELOAD_METHOD_OUTPUTS,
+            move |_: &mut Context, daemon: &mut Daemon, params: ()| {
+                let inputs = crate::dbus::ReloadCmdInputs::from_dbus_input(params);
+                daemon
+                    .reload(inputs)
+                    .map_err(|e| MethodErr::failed(&e.to_string()))?
+                    .to_dbus_output();
+                Ok(())
+            },
+        );
     });
     cr.insert("/", &[iface_token], daemon);
 
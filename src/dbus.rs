@@ -13,6 +13,12 @@ pub const OPEN_METHOD: &str = "Open";
 pub const OPEN_METHOD_INPUTS: (&str,) = ("uri",);
 pub const OPEN_METHOD_OUTPUTS: (&str,) = ("status",);
 
+// dbus-send --print-reply --dest=juif.fabien.choosme / juif.fabien.choosme.OpenMany array:string:"http://example.com","http://example.org"
+
+pub const OPEN_MANY_METHOD: &str = "OpenMany";
+pub const OPEN_MANY_METHOD_INPUTS: (&str,) = ("uris",);
+pub const OPEN_MANY_METHOD_OUTPUTS: (&str, &str) = ("status", "failed");
+
 // dbus-send --print-reply --dest=juif.fabien.choosme / juif.fabien.choosme.Status
 
 pub const STATUS_METHOD: &str = "Status";
@@ -31,6 +37,24 @@ pub const SET_DEFAULT_METHOD: &str = "SetDefault";
 pub const SET_DEFAULT_METHOD_INPUTS: (&str,) = ("index",);
 pub const SET_DEFAULT_METHOD_OUTPUTS: () = ();
 
+// dbus-send --print-reply --dest=juif.fabien.choosme / juif.fabien.choosme.SetDefaultById string:"Firefox"
+
+pub const SET_DEFAULT_BY_ID_METHOD: &str = "SetDefaultById";
+pub const SET_DEFAULT_BY_ID_METHOD_INPUTS: (&str,) = ("id",);
+pub const SET_DEFAULT_BY_ID_METHOD_OUTPUTS: () = ();
+
+// dbus-send --print-reply --dest=juif.fabien.choosme / juif.fabien.choosme.CycleDefault boolean:true
+
+pub const CYCLE_DEFAULT_METHOD: &str = "CycleDefault";
+pub const CYCLE_DEFAULT_METHOD_INPUTS: (&str,) = ("forward",);
+pub const CYCLE_DEFAULT_METHOD_OUTPUTS: () = ();
+
+// dbus-send --print-reply --dest=juif.fabien.choosme / juif.fabien.choosme.Reload
+
+pub const RELOAD_METHOD: &str = "Reload";
+pub const RELOAD_METHOD_INPUTS: () = ();
+pub const RELOAD_METHOD_OUTPUTS: () = ();
+
 #[derive(Debug)]
 pub struct OpenCmdInputs {
     pub uri: String,
@@ -46,6 +70,22 @@ impl OpenCmdInputs {
     }
 }
 
+#[derive(Debug)]
+pub struct OpenManyCmdInputs {
+    pub uris: Vec<String>,
+}
+
+impl OpenManyCmdInputs {
+    pub fn from_dbus_input(input: (Vec<String>,)) -> Self {
+        OpenManyCmdInputs { uris: input.0 }
+    }
+
+    pub fn to_dbus_input(&self) -> (Vec<String>,) {
+        (self.uris.clone(),)
+    }
+}
+
+/// Outputs of `Open`.
 #[derive(Debug)]
 pub struct OpenCmdOutputs {
     pub status: OpenCmdOutputsStatus,
@@ -62,11 +102,33 @@ impl OpenCmdOutputs {
     }
 }
 
+/// Outputs of `OpenMany`, the uris that failed to launch are the only ones to retry.
+#[derive(Debug)]
+pub struct OpenManyCmdOutputs {
+    pub status: OpenCmdOutputsStatus,
+    pub failed: Vec<String>,
+}
+
+impl OpenManyCmdOutputs {
+    pub fn to_dbus_output(&self) -> (String, Vec<String>) {
+        (self.status.clone().into(), self.failed.clone())
+    }
+
+    pub fn from_dbus_output(output: (String, Vec<String>)) -> Result<Self, ToggleStatusParseError> {
+        let status = OpenCmdOutputsStatus::try_from(output.0)?;
+        Ok(OpenManyCmdOutputs {
+            status,
+            failed: output.1,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum OpenCmdOutputsStatus {
     /// No application was launched, the UI was used instead.
+    /// With several uris, some of them may have been launched.
     Fallbacked,
-    /// An application was launched.
+    /// An application was launched, for every uri.
     Launched,
 }
 
@@ -127,8 +189,11 @@ pub struct StatusCmdOutputs {
     pub applications: Vec<StatusCmdOutputApplication>,
 }
 
+/// (id, name, icon, is_default, is_default_rule)
+type DbusApplication = (String, String, String, bool, bool);
+
 impl StatusCmdOutputs {
-    pub fn to_dbus_output(&self) -> (Vec<(String, String, String, bool)>,) {
+    pub fn to_dbus_output(&self) -> (Vec<DbusApplication>,) {
         (self
             .applications
             .iter()
@@ -138,22 +203,26 @@ impl StatusCmdOutputs {
                     app.name.clone(),
                     app.icon.clone(),
                     app.is_default,
+                    app.is_default_rule,
                 )
             })
             .collect(),)
     }
 
-    pub fn from_dbus_output(output: (Vec<(String, String, String, bool)>,)) -> Result<Self, ()> {
+    pub fn from_dbus_output(output: (Vec<DbusApplication>,)) -> Result<Self, ()> {
         Ok(StatusCmdOutputs {
             applications: output
                 .0
                 .into_iter()
-                .map(|(id, name, icon, is_default)| StatusCmdOutputApplication {
-                    id,
-                    name,
-                    icon,
-                    is_default,
-                })
+                .map(
+                    |(id, name, icon, is_default, is_default_rule)| StatusCmdOutputApplication {
+                        id,
+                        name,
+                        icon,
+                        is_default,
+                        is_default_rule,
+                    },
+                )
                 .collect(),
         })
     }
@@ -192,7 +261,10 @@ pub struct StatusCmdOutputApplication {
     pub id: String,
     pub name: String,
     pub icon: String,
+    /// set on the daemon
     pub is_default: bool,
+    /// launched by the default rule active right now
+    pub is_default_rule: bool,
 }
 
 #[derive(Debug)]
@@ -225,6 +297,93 @@ impl SetDefaultCmdOutputs {
     }
 }
 
+#[derive(Debug)]
+pub struct SetDefaultByIdCmdInputs {
+    /// alias, name or id of the application, or id of a choice
+    pub id: String,
+}
+
+impl SetDefaultByIdCmdInputs {
+    pub fn from_dbus_input(input: (String,)) -> Self {
+        Self { id: input.0 }
+    }
+
+    pub fn to_dbus_input(&self) -> (String,) {
+        (self.id.clone(),)
+    }
+}
+
+#[derive(Debug)]
+pub struct SetDefaultByIdCmdOutputs {}
+
+impl SetDefaultByIdCmdOutputs {
+    #[allow(clippy::unused_unit)]
+    pub fn to_dbus_output(&self) -> () {
+        ()
+    }
+
+    pub fn from_dbus_output(_output: ()) -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[derive(Debug)]
+pub struct CycleDefaultCmdInputs {
+    pub forward: bool,
+}
+
+impl CycleDefaultCmdInputs {
+    pub fn from_dbus_input(input: (bool,)) -> Self {
+        Self { forward: input.0 }
+    }
+
+    pub fn to_dbus_input(&self) -> (bool,) {
+        (self.forward,)
+    }
+}
+
+#[derive(Debug)]
+pub struct CycleDefaultCmdOutputs {}
+
+impl CycleDefaultCmdOutputs {
+    #[allow(clippy::unused_unit)]
+    pub fn to_dbus_output(&self) -> () {
+        ()
+    }
+
+    pub fn from_dbus_output(_output: ()) -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[derive(Debug)]
+pub struct ReloadCmdInputs {}
+
+impl ReloadCmdInputs {
+    pub fn from_dbus_input(_input: ()) -> Self {
+        Self {}
+    }
+
+    #[allow(clippy::unused_unit)]
+    pub fn to_dbus_input(&self) -> () {
+        ()
+    }
+}
+
+#[derive(Debug)]
+pub struct ReloadCmdOutputs {}
+
+impl ReloadCmdOutputs {
+    #[allow(clippy::unused_unit)]
+    pub fn to_dbus_output(&self) -> () {
+        ()
+    }
+
+    pub fn from_dbus_output(_output: ()) -> Result<Self> {
+        Ok(Self {})
+    }
+}
+
 pub struct DBUSClient {
     // We remove the proxy from the struct because it borrows from the connection.
     // Instead, we'll create proxies on demand or pass the connection around.
@@ -256,6 +415,20 @@ impl DBUSClient {
         Ok(out)
     }
 
+    pub fn open_many(&self, uris: &[String]) -> Result<OpenManyCmdOutputs> {
+        debug!("sending open_many command with uris: {:?}", uris);
+        let msg = OpenManyCmdInputs {
+            uris: uris.to_vec(),
+        };
+        let result = self
+            .get_proxy()
+            .method_call(DEST, OPEN_MANY_METHOD, msg.to_dbus_input())?;
+        let out = OpenManyCmdOutputs::from_dbus_output(result)
+            .map_err(|e| dbus::Error::new_failed(&e.to_string()))?;
+
+        Ok(out)
+    }
+
     pub fn status(&self) -> Result<StatusCmdOutputs> {
         debug!("sending status command");
         let msg = StatusCmdInputs {};
@@ -290,4 +463,40 @@ impl DBUSClient {
             .map_err(|e| dbus::Error::new_failed(&e.to_string()))?;
         Ok(out)
     }
+
+    pub fn set_default_by_id(&self, id: &str) -> Result<SetDefaultByIdCmdOutputs> {
+        debug!("sending set_default_by_id command with id: {}", id);
+        let msg = SetDefaultByIdCmdInputs { id: id.to_string() };
+        #[allow(clippy::let_unit_value)]
+        let result =
+            self.get_proxy()
+                .method_call(DEST, SET_DEFAULT_BY_ID_METHOD, msg.to_dbus_input())?;
+        let out = SetDefaultByIdCmdOutputs::from_dbus_output(result)
+            .map_err(|e| dbus::Error::new_failed(&e.to_string()))?;
+        Ok(out)
+    }
+
+    pub fn cycle_default(&self, forward: bool) -> Result<CycleDefaultCmdOutputs> {
+        debug!("sending cycle_default command with forward: {}", forward);
+        let msg = CycleDefaultCmdInputs { forward };
+        #[allow(clippy::let_unit_value)]
+        let result =
+            self.get_proxy()
+                .method_call(DEST, CYCLE_DEFAULT_METHOD, msg.to_dbus_input())?;
+        let out = CycleDefaultCmdOutputs::from_dbus_output(result)
+            .map_err(|e| dbus::Error::new_failed(&e.to_string()))?;
+        Ok(out)
+    }
+
+    pub fn reload(&self) -> Result<ReloadCmdOutputs> {
+        debug!("sending reload command");
+        let msg = ReloadCmdInputs {};
+        #[allow(clippy::let_unit_value)]
+        let result = self
+            .get_proxy()
+            .method_call(DEST, RELOAD_METHOD, msg.to_dbus_input())?;
+        let out = ReloadCmdOutputs::from_dbus_output(result)
+            .map_err(|e| dbus::Error::new_failed(&e.to_string()))?;
+        Ok(out)
+    }
 }
@@ -1,15 +1,21 @@
+use anyhow::{Result, format_err};
 use gdk4::gio::AppLaunchContext;
-use gtk4::gio::{DesktopAppInfo, prelude::AppInfoExt};
+use gtk4::gio::{self, AppInfo, DesktopAppInfo, prelude::AppInfoExt};
+use gtk4::glib;
 use std::{
     collections::HashMap,
     env,
-    path::PathBuf,
+    ffi::OsStr,
+    path::{Path, PathBuf},
     sync::mpsc::{self, Sender},
     thread::JoinHandle,
 };
 use tracing::{debug, error, info, warn};
+use url::Url;
+use xdg::BaseDirectories;
 
-use crate::config::Config;
+use crate::config::{Choice, Config, DesktopFileConfig, LaunchOptions};
+use crate::profiles::discover_profiles;
 
 #[derive(Debug)]
 pub struct OpenParams {
@@ -18,12 +24,21 @@ pub struct OpenParams {
     /// The name of the desktop file to launch.
     /// It has to be resolved beforce sending the Launch command. (Via UI for example).
     pub desktop_file_id: String,
+
+    /// Desktop action and extra arguments.
+    pub options: LaunchOptions,
+
+    /// Where to send the launch result, if the sender waits for it.
+    pub reply: Option<Sender<Result<()>>>,
 }
 
 pub enum DesktopFileOpenerCommand {
     /// Open a desktop file by its name.
     Open(OpenParams),
 
+    /// Replace the config and resolve its desktop files again.
+    Reload(Config),
+
     /// Quit.
     Quit,
 }
@@ -32,7 +47,7 @@ pub fn run_desktop_file_opener(cfg: Config) -> (JoinHandle<()>, Sender<DesktopFi
     let (tx, rx) = mpsc::channel();
 
     let jh = std::thread::spawn(move || {
-        let desktop_files = resolve_desktop_files(&cfg);
+        let mut desktop_files = resolve_desktop_files(&cfg);
         debug!("config is parsed and desktop files are resolved");
 
         loop {
@@ -41,6 +56,10 @@ pub fn run_desktop_file_opener(cfg: Config) -> (JoinHandle<()>, Sender<DesktopFi
                     info!("received command to quit desktop file opener");
                     break;
                 }
+                Ok(DesktopFileOpenerCommand::Reload(cfg)) => {
+                    info!("received command to reload desktop files");
+                    desktop_files = resolve_desktop_files(&cfg);
+                }
                 Ok(DesktopFileOpenerCommand::Open(params)) => {
                     info!(
                         "received command to open desktop file with params: {:?}",
@@ -53,18 +72,29 @@ pub fn run_desktop_file_opener(cfg: Config) -> (JoinHandle<()>, Sender<DesktopFi
                         .map(|s| s.as_str())
                         .collect::<Vec<&str>>();
 
-                    let Some(desktop_file) = desktop_files.get(&params.desktop_file_id) else {
-                        error!("no desktop file found for id: {}", params.desktop_file_id);
-                        return;
-                    };
-
                     // open
-                    if let Err(e) = desktop_file.launch_uris(&uris, None::<&AppLaunchContext>) {
+                    // ids outside of the config are system handlers, see `scheme_handler`
+                    let desktop_file = desktop_files
+                        .get(&params.desktop_file_id)
+                        .cloned()
+                        .or_else(|| DesktopAppInfo::new(&params.desktop_file_id));
+                    let result = match desktop_file {
+                        Some(desktop_file) => launch(&desktop_file, &uris, &params.options),
+                        None => Err(format_err!(
+                            "no desktop file found for id: {}",
+                            params.desktop_file_id
+                        )),
+                    };
+                    if let Err(e) = &result {
                         error!(
                             "failed to open desktop file '{}': {}",
                             params.desktop_file_id, e
                         );
                     }
+                    if let Some(reply) = params.reply {
+                        // the sender may have given up waiting
+                        let _ = reply.send(result);
+                    }
                 }
                 Err(_) => {
                     error!("error receiving command from init_desktop_files channel");
@@ -77,43 +107,374 @@ pub fn run_desktop_file_opener(cfg: Config) -> (JoinHandle<()>, Sender<DesktopFi
     (jh, tx)
 }
 
-pub fn resolve_desktop_files(config_file: &Config) -> HashMap<String, DesktopAppInfo> {
-    let home_dir_str = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok();
+/// Launches the desktop file, or its action, with extra arguments before the uris.
+fn launch(desktop_file: &DesktopAppInfo, uris: &[&str], options: &LaunchOptions) -> Result<()> {
+    if options.is_empty() {
+        desktop_file.launch_uris(uris, None::<&AppLaunchContext>)?;
+        return Ok(());
+    }
 
+    let exec = match &options.action {
+        Some(action) => action_exec(desktop_file, action)?,
+        None => desktop_file
+            .commandline()
+            .map(|c| c.to_string_lossy().to_string())
+            .ok_or_else(|| format_err!("desktop file has no Exec"))?,
+    };
+
+    let exec_argv = glib::shell_parse_argv(&exec)?
+        .into_iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    // the field codes are expanded here, GIO would quote them with the uris
+    for argv in exec_commands(&exec_argv, &options.args, uris) {
+        debug!("launching: {:?}", argv);
+        let argv = argv.iter().map(OsStr::new).collect::<Vec<_>>();
+        gio::Subprocess::newv(&argv, gio::SubprocessFlags::NONE)?;
+    }
+    Ok(())
+}
+
+/// Command lines of an Exec line, with the extra arguments before the uris.
+/// Like GIO, `%U` and `%F` take every uri in one command, `%u` and `%f` one uri per command.
+/// Without uris field code, the uris are appended.
+fn exec_commands(exec_argv: &[String], args: &[String], uris: &[&str]) -> Vec<Vec<String>> {
+    let one_per_command = exec_argv
+        .iter()
+        .any(|arg| matches!(arg.as_str(), "%u" | "%f"));
+    let has_uris_field = exec_argv.iter().any(|arg| is_uris_field_code(arg));
+    let batches: Vec<&[&str]> = if one_per_command && !uris.is_empty() {
+        uris.chunks(1).collect()
+    } else {
+        vec![uris]
+    };
+
+    batches
+        .into_iter()
+        .map(|batch| {
+            let mut argv = Vec::new();
+            for arg in exec_argv {
+                match arg.as_str() {
+                    "%u" | "%U" => {
+                        argv.extend(args.iter().cloned());
+                        argv.extend(batch.iter().map(|uri| uri.to_string()));
+                    }
+                    "%f" | "%F" => {
+                        argv.extend(args.iter().cloned());
+                        argv.extend(batch.iter().map(|uri| file_path_or_uri(uri)));
+                    }
+                    _ if is_field_code(arg) => {}
+                    _ => argv.push(arg.replace("%%", "%")),
+                }
+            }
+            if !has_uris_field {
+                argv.extend(args.iter().cloned());
+                argv.extend(batch.iter().map(|uri| uri.to_string()));
+            }
+            argv
+        })
+        .collect()
+}
+
+/// `%f` and `%F` take paths, other uris are given as they are.
+fn file_path_or_uri(uri: &str) -> String {
+    Url::parse(uri)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|| uri.to_string())
+}
+
+/// `Exec` of a `[Desktop Action <action>]` group.
+fn action_exec(desktop_file: &DesktopAppInfo, action: &str) -> Result<String> {
+    if !desktop_file.list_actions().iter().any(|a| a == action) {
+        return Err(format_err!("unknown desktop action: {}", action));
+    }
+    let path = desktop_file
+        .filename()
+        .ok_or_else(|| format_err!("desktop file has no filename"))?;
+    let key_file = glib::KeyFile::new();
+    key_file.load_from_file(&path, glib::KeyFileFlags::NONE)?;
+    Ok(key_file
+        .string(&format!("Desktop Action {action}"), "Exec")?
+        .to_string())
+}
+
+fn is_uris_field_code(arg: &str) -> bool {
+    matches!(arg, "%u" | "%U" | "%f" | "%F")
+}
+
+fn is_field_code(arg: &str) -> bool {
+    matches!(
+        arg,
+        "%u" | "%U" | "%f" | "%F" | "%i" | "%c" | "%k" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m"
+    )
+}
+
+/// Desktop id of the default application for an uri scheme, unless it is choosme itself.
+pub fn scheme_handler(scheme: &str) -> Option<String> {
+    let app_info = AppInfo::default_for_uri_scheme(scheme)?;
+    let id = app_info.id()?.to_string();
+    if id == format!("{}.desktop", env!("CARGO_PKG_NAME")) {
+        return None;
+    }
+    Some(id)
+}
+
+/// Desktop action opening a private window, eg: `new-private-window` or `new-incognito-window`.
+pub fn private_action(desktop_file: &DesktopAppInfo) -> Option<String> {
+    desktop_file
+        .list_actions()
+        .into_iter()
+        .map(|action| action.to_string())
+        .find(|action| {
+            let action = action.to_lowercase();
+            action.contains("private") || action.contains("incognito")
+        })
+}
+
+/// Mime type of a file, guessed from its name.
+pub fn guess_mime_type(path: &Path) -> Option<String> {
+    let (content_type, _) = gio::content_type_guess(Some(path), &[]);
+    gio::content_type_get_mime_type(&content_type).map(|m| m.to_string())
+}
+
+pub fn resolve_desktop_files(config_file: &Config) -> HashMap<String, DesktopAppInfo> {
     let mut res = HashMap::new();
     for file in config_file.desktop_files.iter() {
-        let desktop_file_path_str = &file.path;
-        let mut desktop_file_path_buf = PathBuf::from(desktop_file_path_str);
-
-        if let Some(end) = desktop_file_path_str.strip_prefix("~/") {
-            if let Some(h_dir_path_str) = home_dir_str.as_ref() {
-                let mut h_dir_path_buf = PathBuf::from(h_dir_path_str);
-                h_dir_path_buf.push(end);
-                desktop_file_path_buf = h_dir_path_buf;
-            } else {
-                warn!(
-                    "unable to to resolve '~' in path: {}",
-                    desktop_file_path_str
-                );
-                continue;
-            }
+        if let Some(app_info) = resolve_desktop_file(file) {
+            res.insert(file.id.clone(), app_info);
         }
-        let desktop_file_path = desktop_file_path_buf.as_path();
-        if !desktop_file_path.exists() {
-            warn!(
-                "desktop file not found, skipping: {}",
-                desktop_file_path_str
-            );
+    }
+    res
+}
+
+/// Resolved applications in config order, each followed by its desktop actions
+/// if `expand_actions` is set, or by its browser profiles if `profiles` is set.
+pub fn resolve_choices(
+    cfg: &Config,
+    desktop_files: &HashMap<String, DesktopAppInfo>,
+) -> Vec<Choice> {
+    let mut choices = Vec::new();
+    for desktop_file_config in cfg.desktop_files.iter() {
+        let Some(desktop_file) = desktop_files.get(&desktop_file_config.id) else {
+            continue;
+        };
+        let name = desktop_file_config
+            .alias
+            .clone()
+            .unwrap_or_else(|| desktop_file.name().to_string());
+        choices.push(Choice {
+            id: desktop_file_config.id.clone(),
+            desktop_file_id: desktop_file_config.id.clone(),
+            name: name.clone(),
+            color: None,
+            key: desktop_file_config.key(),
+            options: desktop_file_config.launch_options(),
+        });
+        if let Some(kind) = desktop_file_config.profiles {
+            let profiles =
+                match discover_profiles(kind, desktop_file_config.profiles_dir.as_deref()) {
+                    Ok(profiles) => profiles,
+                    Err(e) => {
+                        warn!(
+                            "failed to list profiles of '{}': {}",
+                            desktop_file_config.id, e
+                        );
+                        continue;
+                    }
+                };
+            choices.extend(profiles.into_iter().map(|profile| Choice {
+                id: format!("{}:{}", desktop_file_config.id, profile.id),
+                desktop_file_id: desktop_file_config.id.clone(),
+                name: format!("{} - {}", name, profile.name),
+                color: profile.color,
+                key: None,
+                options: desktop_file_config.profile_launch_options(&profile.id),
+            }));
+        }
+        if desktop_file_config.expand_actions != Some(true) {
             continue;
         }
-        let Some(app_info) = DesktopAppInfo::from_filename(desktop_file_path) else {
+        for action in desktop_file.list_actions() {
+            choices.push(Choice {
+                id: format!("{}:{}", desktop_file_config.id, action),
+                desktop_file_id: desktop_file_config.id.clone(),
+                name: format!("{} - {}", name, desktop_file.action_name(&action)),
+                color: None,
+                key: None,
+                options: desktop_file_config.action_launch_options(&action),
+            });
+        }
+    }
+    choices
+}
+
+pub fn resolve_desktop_file(file: &DesktopFileConfig) -> Option<DesktopAppInfo> {
+    match (&file.desktop_id, &file.path) {
+        // the id is the desktop id with its `.desktop` suffix
+        (Some(_), _) => resolve_desktop_id(&file.id),
+        (None, Some(path)) => resolve_desktop_path(path),
+        (None, None) => None,
+    }
+}
+
+fn resolve_desktop_path(desktop_file_path_str: &str) -> Option<DesktopAppInfo> {
+    let mut desktop_file_path_buf = PathBuf::from(desktop_file_path_str);
+
+    if let Some(end) = desktop_file_path_str.strip_prefix("~/") {
+        let Some(h_dir_path_str) = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
+        else {
             warn!(
-                "unknown or corrupted desktop file '{:?}'",
-                desktop_file_path
+                "unable to to resolve '~' in path: {}",
+                desktop_file_path_str
             );
-            continue;
+            return None;
         };
-        res.insert(file.id.clone(), app_info);
+        let mut h_dir_path_buf = PathBuf::from(h_dir_path_str);
+        h_dir_path_buf.push(end);
+        desktop_file_path_buf = h_dir_path_buf;
+    }
+    let desktop_file_path = desktop_file_path_buf.as_path();
+    if !desktop_file_path.exists() {
+        warn!(
+            "desktop file not found, skipping: {}",
+            desktop_file_path_str
+        );
+        return None;
+    }
+    let app_info = DesktopAppInfo::from_filename(desktop_file_path);
+    if app_info.is_none() {
+        warn!(
+            "unknown or corrupted desktop file '{:?}'",
+            desktop_file_path
+        );
+    }
+    app_info
+}
+
+/// Resolves a desktop file ID (eg: `firefox.desktop`) the same way
+/// `DesktopAppInfo::new` does, but also looking into Flatpak and Snap exports
+/// which are not always part of `XDG_DATA_DIRS`.
+fn resolve_desktop_id(desktop_id: &str) -> Option<DesktopAppInfo> {
+    if let Some(app_info) = DesktopAppInfo::new(desktop_id) {
+        return Some(app_info);
+    }
+
+    for dir in applications_dirs() {
+        if let Some(path) = find_desktop_id_in(&dir, desktop_id) {
+            debug!("desktop id '{}' found in {}", desktop_id, path.display());
+            if let Some(app_info) = DesktopAppInfo::from_filename(&path) {
+                return Some(app_info);
+            }
+            warn!("unknown or corrupted desktop file '{:?}'", path);
+        }
+    }
+
+    warn!("desktop id not found, skipping: {}", desktop_id);
+    None
+}
+
+/// `applications` directories, by order of precedence.
+pub fn applications_dirs() -> Vec<PathBuf> {
+    let xdg_dirs = BaseDirectories::new();
+    let mut dirs = Vec::new();
+    if let Some(data_home) = xdg_dirs.get_data_home() {
+        dirs.push(data_home.join("applications"));
+        dirs.push(data_home.join("flatpak/exports/share/applications"));
+    }
+    for data_dir in xdg_dirs.get_data_dirs() {
+        dirs.push(data_dir.join("applications"));
+    }
+    dirs.push(PathBuf::from("/var/lib/flatpak/exports/share/applications"));
+    dirs.push(PathBuf::from("/var/lib/snapd/desktop/applications"));
+
+    let mut unique = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// A desktop ID is the path relative to the `applications` directory
+/// with `/` replaced by `-`, so `foo-bar.desktop` can be `foo/bar.desktop`.
+fn find_desktop_id_in(dir: &Path, desktop_id: &str) -> Option<PathBuf> {
+    let path = dir.join(desktop_id);
+    if path.is_file() {
+        return Some(path);
+    }
+    for (index, _) in desktop_id.match_indices('-') {
+        let sub_dir = dir.join(&desktop_id[..index]);
+        if sub_dir.is_dir()
+            && let Some(path) = find_desktop_id_in(&sub_dir, &desktop_id[index + 1..])
+        {
+            return Some(path);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    const URIS: &[&str] = &["https://a.com/", "https://b.com/"];
+
+    #[test]
+    fn exec_commands_with_single_uri_field_code() {
+        assert_eq!(
+            exec_commands(&argv(&["firefox", "%u"]), &argv(&["-P", "work"]), URIS),
+            vec![
+                argv(&["firefox", "-P", "work", "https://a.com/"]),
+                argv(&["firefox", "-P", "work", "https://b.com/"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn exec_commands_with_uris_field_code() {
+        assert_eq!(
+            exec_commands(
+                &argv(&["chromium", "--incognito", "%U", "%i"]),
+                &argv(&["--profile-directory=Profile 2"]),
+                URIS
+            ),
+            vec![argv(&[
+                "chromium",
+                "--incognito",
+                "--profile-directory=Profile 2",
+                "https://a.com/",
+                "https://b.com/",
+            ])]
+        );
+    }
+
+    #[test]
+    fn exec_commands_without_field_code() {
+        assert_eq!(
+            exec_commands(&argv(&["browser", "100%%"]), &argv(&["--new"]), URIS),
+            vec![argv(&[
+                "browser",
+                "100%",
+                "--new",
+                "https://a.com/",
+                "https://b.com/",
+            ])]
+        );
+    }
+
+    #[test]
+    fn exec_commands_with_files() {
+        assert_eq!(
+            exec_commands(&argv(&["evince", "%f"]), &[], &["file:///tmp/a%20b.pdf"]),
+            vec![argv(&["evince", "/tmp/a b.pdf"])]
+        );
     }
-    res
 }
@@ -0,0 +1,152 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::config::{Config, Decision, LaunchOptions, MatchReason};
+use crate::rewrite::rewrite_uri;
+
+/// Dry-run of the rules for an uri, nothing is launched.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub uri: String,
+    /// uri after the rewrite stage, this is the one matched
+    pub rewritten_uri: String,
+    /// evaluated rules in order, evaluation stops at the first match
+    pub rules: Vec<RuleEvaluation>,
+    pub daemon_running: bool,
+    /// default application set on the daemon
+    pub daemon_default: Option<String>,
+    pub decision: DecisionOutput,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleEvaluation {
+    pub rule: String,
+    pub application: String,
+    pub matched: Option<MatchReason>,
+    /// exclusion that made a matching rule not match
+    pub excluded_by: Option<MatchReason>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DecisionOutput {
+    Launch {
+        application: String,
+        reason: String,
+        options: LaunchOptions,
+    },
+    Ui,
+}
+
+/// `daemon_default` is only used when the daemon is running, `now` for the default rules schedules.
+pub fn explain(
+    cfg: &Config,
+    uri: &str,
+    daemon_running: bool,
+    daemon_default: Option<String>,
+    now: NaiveDateTime,
+) -> Explanation {
+    let rewritten_uri = rewrite_uri(&cfg.rewrite, uri);
+    let uri_to_match = rewritten_uri.as_str();
+    let rules = cfg
+        .evaluate(uri_to_match)
+        .into_iter()
+        .map(|evaluation| RuleEvaluation {
+            rule: evaluation.rule.source.to_string(),
+            application: evaluation.rule.application_reference().to_string(),
+            matched: evaluation.matched,
+            excluded_by: evaluation.excluded_by,
+        })
+        .collect();
+
+    let decision = cfg.decide(uri_to_match, daemon_default.as_deref(), now);
+    let options = decision.launch_options(cfg);
+    let decision_output = match &decision {
+        Decision::Ui => DecisionOutput::Ui,
+        Decision::Rule(rule, reason) => DecisionOutput::Launch {
+            application: rule.application_reference().to_string(),
+            reason: format!("{} matched with {}", rule.source, reason),
+            options,
+        },
+        Decision::SchemeHandler { desktop_id, scheme } => DecisionOutput::Launch {
+            application: desktop_id.clone(),
+            reason: format!("default handler for {}:", scheme),
+            options,
+        },
+        Decision::RuntimeDefault { choice_id, .. } => DecisionOutput::Launch {
+            application: choice_id.to_string(),
+            reason: "daemon default application".to_string(),
+            options,
+        },
+        Decision::DefaultRule(rule) => DecisionOutput::Launch {
+            application: rule.application_reference().to_string(),
+            reason: match &rule.schedule {
+                Some(schedule) => format!("{} is the default rule ({})", rule.source, schedule),
+                None => format!("{} is the default rule", rule.source),
+            },
+            options,
+        },
+    };
+
+    Explanation {
+        uri: uri.to_string(),
+        rewritten_uri,
+        rules,
+        daemon_running,
+        daemon_default,
+        decision: decision_output,
+    }
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "uri: {}", self.uri)?;
+        if self.rewritten_uri != self.uri {
+            writeln!(f, "rewritten uri: {}", self.rewritten_uri)?;
+        }
+        if self.rules.is_empty() {
+            writeln!(f, "no rule evaluated")?;
+        }
+        for evaluation in &self.rules {
+            match (&evaluation.matched, &evaluation.excluded_by) {
+                (Some(reason), Some(exclusion)) => writeln!(
+                    f,
+                    "{} ({}): matched with {} but excluded by {}",
+                    evaluation.rule, evaluation.application, reason, exclusion
+                )?,
+                (Some(reason), None) => writeln!(
+                    f,
+                    "{} ({}): matched with {}",
+                    evaluation.rule, evaluation.application, reason
+                )?,
+                (None, _) => writeln!(
+                    f,
+                    "{} ({}): no match",
+                    evaluation.rule, evaluation.application
+                )?,
+            }
+        }
+        match (&self.daemon_default, self.daemon_running) {
+            (_, false) => writeln!(f, "daemon: not running")?,
+            (Some(default), true) => writeln!(f, "daemon default: {}", default)?,
+            (None, true) => writeln!(f, "daemon default: none")?,
+        }
+        match &self.decision {
+            DecisionOutput::Launch {
+                application,
+                reason,
+                options,
+            } => {
+                write!(f, "decision: launch '{}' ({})", application, reason)?;
+                if let Some(action) = &options.action {
+                    write!(f, " with action '{}'", action)?;
+                }
+                if !options.args.is_empty() {
+                    write!(f, " with args {:?}", options.args)?;
+                }
+                Ok(())
+            }
+            DecisionOutput::Ui => write!(f, "decision: fall back to UI"),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+/// Letters of the query found in order in a text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    /// higher is better
+    pub score: i64,
+    /// indices of the matched chars in the text
+    pub positions: Vec<usize>,
+}
+
+/// Case insensitive subsequence match, consecutive letters and word starts score higher.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let text = text.chars().collect::<Vec<_>>();
+    let mut positions: Vec<usize> = Vec::new();
+    let mut score = 0;
+    let mut start = 0;
+    for query_char in query.chars() {
+        let offset = text[start..]
+            .iter()
+            .position(|c| same_letter(*c, query_char))?;
+        let position = start + offset;
+        score += 1;
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match positions.last() {
+            Some(last) if last + 1 == position => score += 4,
+            // letters far away from the previous one are a weak match
+            _ => score -= offset.min(8) as i64,
+        }
+        positions.push(position);
+        start = position + 1;
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+fn same_letter(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase())
+}
@@ -0,0 +1,100 @@
+use anyhow::{Result, format_err};
+use gtk4::gio::{AppInfo, prelude::AppInfoExt};
+use std::{fs, io::Write, path::Path};
+use tracing::{debug, info};
+
+const BROWSER_CONTENT_TYPES: [&str; 2] = ["x-scheme-handler/http", "x-scheme-handler/https"];
+
+/// Every installed application handling http or https links, except choosme itself.
+pub fn list_browsers() -> Vec<AppInfo> {
+    let own_desktop_id = format!("{}.desktop", env!("CARGO_PKG_NAME"));
+
+    let mut browsers: Vec<AppInfo> = Vec::new();
+    for content_type in BROWSER_CONTENT_TYPES {
+        for app_info in AppInfo::all_for_type(content_type) {
+            let Some(id) = app_info.id() else {
+                continue;
+            };
+            if id == own_desktop_id || browsers.iter().any(|b| b.id() == Some(id.clone())) {
+                continue;
+            }
+            debug!("found browser: {}", id);
+            browsers.push(app_info);
+        }
+    }
+    browsers
+}
+
+/// Generates a commented config with one `[[application]]` per browser.
+pub fn generate_config(browsers: &[AppInfo]) -> String {
+    let mut content = String::from(
+        "# generated by `choosme init`, see https://github.com/fabienjuif/choosme#config\n\
+         \n\
+         # rules are evaluated in file order, the first matching rule wins\n\
+         # [[rule]]\n\
+         # prefixes = [\"https://github.com/\"]\n\
+         # application = \"Firefox\"\n\
+         \n\
+         # optional fallback rule if you want to avoid the UI to pop\n\
+         # [[rule]]\n\
+         # default = \"Firefox\"\n",
+    );
+
+    if browsers.is_empty() {
+        content.push_str(
+            "\n# no browser found, add your applications here\n\
+             # [[application]]\n\
+             # desktop_id = \"firefox.desktop\"\n\
+             # name = \"Firefox\"\n",
+        );
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for browser in browsers {
+        let Some(desktop_id) = browser.id() else {
+            continue;
+        };
+        // names are used by rules, so they have to be unique
+        let base_name = browser.name().to_string();
+        let mut name = base_name.clone();
+        let mut suffix = 2;
+        while names.contains(&name) {
+            name = format!("{base_name} {suffix}");
+            suffix += 1;
+        }
+
+        content.push_str(&format!(
+            "\n[[application]]\ndesktop_id = {}\nname = {}\n# alias = \"shown instead of the desktop file name\"\n",
+            toml::Value::String(desktop_id.to_string()),
+            toml::Value::String(name.clone()),
+        ));
+        names.push(name);
+    }
+
+    content
+}
+
+/// Writes a generated config file, never overwriting an existing one.
+pub fn init_config(config_path: &Path) -> Result<()> {
+    let browsers = list_browsers();
+    info!(
+        "generating config with {} browser(s): {}",
+        browsers.len(),
+        config_path.display()
+    );
+    let content = generate_config(&browsers);
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(config_path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                format_err!("config file already exists: {}", config_path.display())
+            }
+            _ => e.into(),
+        })?;
+    file.write_all(content.as_bytes())?;
+
+    Ok(())
+}
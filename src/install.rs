@@ -0,0 +1,351 @@
+use anyhow::{Result, format_err};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+use xdg::BaseDirectories;
+
+const DESKTOP_ENTRY: &str = include_str!("../choosme.desktop");
+const DEFAULT_APPLICATIONS_SECTION: &str = "[Default Applications]";
+
+fn desktop_file_name() -> String {
+    format!("{}.desktop", env!("CARGO_PKG_NAME"))
+}
+
+/// Files touched by install and uninstall.
+pub struct InstallPaths {
+    /// `$XDG_DATA_HOME/applications/choosme.desktop`
+    pub desktop_file: PathBuf,
+    /// `$XDG_CONFIG_HOME/mimeapps.list`
+    pub mimeapps_list: PathBuf,
+    /// `$XDG_STATE_HOME/choosme/install.toml`, previous defaults to restore on uninstall
+    pub record: PathBuf,
+}
+
+impl InstallPaths {
+    pub fn from_xdg() -> Result<Self> {
+        let xdg_dirs = BaseDirectories::new();
+        let data_home = xdg_dirs
+            .get_data_home()
+            .ok_or_else(|| format_err!("unable to find XDG_DATA_HOME"))?;
+        let config_home = xdg_dirs
+            .get_config_home()
+            .ok_or_else(|| format_err!("unable to find XDG_CONFIG_HOME"))?;
+        let app_xdg_dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+
+        Ok(Self {
+            desktop_file: data_home.join("applications").join(desktop_file_name()),
+            mimeapps_list: config_home.join("mimeapps.list"),
+            record: app_xdg_dirs.place_state_file("install.toml")?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct InstallRecord {
+    /// mime type -> default applications before install, the whole `mimeapps.list` value
+    #[serde(default)]
+    previous_defaults: BTreeMap<String, String>,
+}
+
+/// Installs the bundled desktop entry and registers it as the default for its mime types.
+pub fn install(paths: &InstallPaths, exec: &Path) -> Result<()> {
+    let desktop_file_name = desktop_file_name();
+
+    // desktop entry
+    if let Some(parent) = paths.desktop_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&paths.desktop_file, desktop_entry(exec))?;
+    info!("desktop file written: {}", paths.desktop_file.display());
+
+    // mimeapps.list
+    let mut mimeapps = read_optional(&paths.mimeapps_list)?;
+    let mut record = read_record(&paths.record)?;
+    for mime_type in mime_types() {
+        let previous = get_default(&mimeapps, mime_type);
+        debug!("previous default for {}: {:?}", mime_type, previous);
+        match previous {
+            // installing twice should not record choosme as the previous default
+            Some(previous) if first_desktop_id(&previous) != Some(desktop_file_name.as_str()) => {
+                record
+                    .previous_defaults
+                    .insert(mime_type.to_string(), previous);
+            }
+            _ => {}
+        }
+        mimeapps = set_default(&mimeapps, mime_type, Some(&format!("{desktop_file_name};")));
+    }
+    if let Some(parent) = paths.mimeapps_list.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&paths.mimeapps_list, mimeapps)?;
+    info!("mimeapps.list updated: {}", paths.mimeapps_list.display());
+
+    fs::write(&paths.record, toml::to_string(&record)?)?;
+    debug!("install record written: {}", paths.record.display());
+
+    Ok(())
+}
+
+/// Removes the desktop entry and restores the defaults recorded on install.
+pub fn uninstall(paths: &InstallPaths) -> Result<()> {
+    let desktop_file_name = desktop_file_name();
+    let record = read_record(&paths.record)?;
+
+    let mut mimeapps = read_optional(&paths.mimeapps_list)?;
+    for mime_type in mime_types() {
+        let current = get_default(&mimeapps, mime_type);
+        if current.as_deref().and_then(first_desktop_id) != Some(desktop_file_name.as_str()) {
+            // the user changed it since install, we leave it alone
+            continue;
+        }
+        let previous = record.previous_defaults.get(mime_type);
+        debug!("restoring default for {}: {:?}", mime_type, previous);
+        mimeapps = set_default(&mimeapps, mime_type, previous.map(|p| p.as_str()));
+    }
+    fs::write(&paths.mimeapps_list, mimeapps)?;
+    info!("mimeapps.list restored: {}", paths.mimeapps_list.display());
+
+    match fs::remove_file(&paths.desktop_file) {
+        Ok(_) => info!("desktop file removed: {}", paths.desktop_file.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            warn!("desktop file not found: {}", paths.desktop_file.display())
+        }
+        Err(e) => return Err(e.into()),
+    }
+    match fs::remove_file(&paths.record) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(())
+}
+
+/// Bundled desktop entry with `Exec=` pointing to the given binary.
+fn desktop_entry(exec: &Path) -> String {
+    let exec = exec.to_string_lossy();
+    // desktop entry spec: arguments with reserved characters have to be quoted
+    let exec = if exec.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", exec.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        exec.to_string()
+    };
+
+    DESKTOP_ENTRY
+        .lines()
+        .map(|line| {
+            if line.starts_with("Exec=") {
+                format!("Exec={exec} %U")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Mime types declared in the bundled desktop entry.
+fn mime_types() -> Vec<&'static str> {
+    DESKTOP_ENTRY
+        .lines()
+        .find_map(|line| line.strip_prefix("MimeType="))
+        .map(|mime_types| mime_types.split(';').filter(|m| !m.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn read_optional(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_record(path: &Path) -> Result<InstallRecord> {
+    let content = read_optional(path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// Default applications for a mime type in a mimeapps.list content,
+/// the value as it is written, eg: `firefox.desktop;chromium.desktop;`.
+fn get_default(mimeapps: &str, mime_type: &str) -> Option<String> {
+    let mut in_section = false;
+    for line in mimeapps.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == DEFAULT_APPLICATIONS_SECTION;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=')
+            && key.trim() == mime_type
+        {
+            return Some(value.trim().to_string());
+        }
+    }
+    None
+}
+
+/// The value is a list, the first one is the default.
+fn first_desktop_id(value: &str) -> Option<&str> {
+    value.split(';').map(|v| v.trim()).find(|v| !v.is_empty())
+}
+
+/// Sets (or removes with `None`) the default applications for a mime type,
+/// keeping every other line untouched.
+fn set_default(mimeapps: &str, mime_type: &str, value: Option<&str>) -> String {
+    let new_line = value.map(|value| format!("{mime_type}={value}"));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_section = false;
+    let mut section_found = false;
+    let mut done = false;
+    for line in mimeapps.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            // leaving the section without finding the key: appending it
+            if in_section && !done {
+                if let Some(new_line) = &new_line {
+                    insert_before_blank_lines(&mut lines, new_line.clone());
+                }
+                done = true;
+            }
+            in_section = trimmed == DEFAULT_APPLICATIONS_SECTION;
+            section_found |= in_section;
+            lines.push(line.to_string());
+            continue;
+        }
+        if in_section
+            && let Some((key, _)) = trimmed.split_once('=')
+            && key.trim() == mime_type
+        {
+            if let Some(new_line) = &new_line
+                && !done
+            {
+                lines.push(new_line.clone());
+            }
+            done = true;
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+
+    if !done && let Some(new_line) = new_line {
+        if !section_found {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(DEFAULT_APPLICATIONS_SECTION.to_string());
+        }
+        lines.push(new_line);
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn insert_before_blank_lines(lines: &mut Vec<String>, line: String) {
+    let index = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    lines.insert(index, line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIMEAPPS: &str = "[Added Associations]
+x-scheme-handler/http=firefox.desktop;
+
+[Default Applications]
+x-scheme-handler/http=firefox.desktop;chromium.desktop;
+text/plain=org.gnome.TextEditor.desktop;
+";
+
+    /// Install paths in a temporary XDG directory, specific to each test.
+    fn temp_paths(test: &str) -> InstallPaths {
+        let dir = env::temp_dir().join(format!("choosme-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        InstallPaths {
+            desktop_file: dir.join("applications").join(desktop_file_name()),
+            mimeapps_list: dir.join("mimeapps.list"),
+            record: dir.join("install.toml"),
+        }
+    }
+
+    #[test]
+    fn install_then_uninstall_restores_mimeapps() {
+        let paths = temp_paths("round-trip");
+        fs::write(&paths.mimeapps_list, MIMEAPPS).unwrap();
+
+        install(&paths, Path::new("/usr/bin/choosme")).unwrap();
+        let installed = fs::read_to_string(&paths.mimeapps_list).unwrap();
+        assert_eq!(
+            get_default(&installed, "x-scheme-handler/http").as_deref(),
+            Some("choosme.desktop;")
+        );
+        assert!(paths.desktop_file.exists());
+
+        uninstall(&paths).unwrap();
+        let uninstalled = fs::read_to_string(&paths.mimeapps_list).unwrap();
+        assert_eq!(
+            get_default(&uninstalled, "x-scheme-handler/http").as_deref(),
+            Some("firefox.desktop;chromium.desktop;")
+        );
+        assert_eq!(
+            get_default(&uninstalled, "text/plain").as_deref(),
+            Some("org.gnome.TextEditor.desktop;")
+        );
+        assert_eq!(get_default(&uninstalled, "text/html"), None);
+        assert!(!paths.desktop_file.exists());
+        assert!(!paths.record.exists());
+    }
+
+    #[test]
+    fn install_twice_keeps_the_previous_defaults() {
+        let paths = temp_paths("twice");
+        fs::write(&paths.mimeapps_list, MIMEAPPS).unwrap();
+
+        install(&paths, Path::new("/usr/bin/choosme")).unwrap();
+        install(&paths, Path::new("/usr/bin/choosme")).unwrap();
+        uninstall(&paths).unwrap();
+
+        let uninstalled = fs::read_to_string(&paths.mimeapps_list).unwrap();
+        assert_eq!(
+            get_default(&uninstalled, "x-scheme-handler/http").as_deref(),
+            Some("firefox.desktop;chromium.desktop;")
+        );
+    }
+
+    #[test]
+    fn uninstall_keeps_defaults_changed_by_the_user() {
+        let paths = temp_paths("changed");
+        fs::write(&paths.mimeapps_list, MIMEAPPS).unwrap();
+
+        install(&paths, Path::new("/usr/bin/choosme")).unwrap();
+        let installed = fs::read_to_string(&paths.mimeapps_list).unwrap();
+        let changed = set_default(&installed, "x-scheme-handler/http", Some("brave.desktop;"));
+        fs::write(&paths.mimeapps_list, changed).unwrap();
+        uninstall(&paths).unwrap();
+
+        let uninstalled = fs::read_to_string(&paths.mimeapps_list).unwrap();
+        assert_eq!(
+            get_default(&uninstalled, "x-scheme-handler/http").as_deref(),
+            Some("brave.desktop;")
+        );
+        // the other ones are still restored
+        assert_eq!(get_default(&uninstalled, "x-scheme-handler/https"), None);
+    }
+}
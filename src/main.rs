@@ -1,8 +1,18 @@
+mod check;
 mod cli;
 mod config;
 mod daemon;
 mod dbus;
 mod desktop_files;
+mod explain;
+mod fuzzy;
+mod init;
+mod install;
+mod profiles;
+mod remember;
+mod rewrite;
+mod schedule;
+mod state;
 mod ui;
 
 use anyhow::{Result, format_err};
@@ -10,6 +20,7 @@ use daemon::register_dbus;
 use desktop_files::run_desktop_file_opener;
 use gtk4::gio::prelude::ApplicationExtManual;
 use gtk4::glib::ExitCode;
+use schedule::{Clock, SystemClock};
 use std::env;
 use std::path::PathBuf;
 use std::sync::mpsc;
@@ -42,7 +53,13 @@ fn run() -> Result<()> {
 
     // parsing arguments
     let mut daemon_mode = false;
-    let cli = cli::parse();
+    let mut cli = cli::parse();
+    // relative paths only make sense here, not in the daemon
+    cli.uris = cli
+        .uris
+        .iter()
+        .map(|arg| rewrite::uri_from_arg(arg))
+        .collect();
     match cli.command {
         Some(cli::Commands::Daemon {
             set_default,
@@ -50,6 +67,8 @@ fn run() -> Result<()> {
             status,
             kill,
             set_default_next,
+            set_default_prev,
+            reload,
             waybar,
         }) => {
             if !status
@@ -57,6 +76,8 @@ fn run() -> Result<()> {
                 && set_default.is_none()
                 && !kill
                 && !set_default_next
+                && !set_default_prev
+                && !reload
                 && !waybar
             {
                 daemon_mode = true;
@@ -77,37 +98,36 @@ fn run() -> Result<()> {
                         .kill()
                         .map_err(|e| format_err!("on dbus_client.kill(): {e}"))?;
                     return Ok(());
-                } else if let Some(index) = set_default {
-                    let _ = dbus_client
-                        .set_default(index as i64)
-                        .map_err(|e| format_err!("on dbus_client.set_default(): {e}"))?;
+                } else if let Some(application) = set_default {
+                    // indexes are kept for existing scripts, anything else is an alias or an id
+                    match application.parse::<u64>() {
+                        Ok(index) => {
+                            let _ = dbus_client
+                                .set_default(index as i64)
+                                .map_err(|e| format_err!("on dbus_client.set_default(): {e}"))?;
+                        }
+                        Err(_) => {
+                            let _ = dbus_client.set_default_by_id(&application).map_err(|e| {
+                                format_err!("on dbus_client.set_default_by_id(): {e}")
+                            })?;
+                        }
+                    }
                     return Ok(());
                 } else if unset_default {
                     let _ = dbus_client
                         .set_default(-1)
                         .map_err(|e| format_err!("on dbus_client.set_default(-1): {e}"))?;
                     return Ok(());
-                } else if set_default_next {
-                    // getting status
-                    let status = dbus_client
-                        .status()
-                        .map_err(|e| format_err!("on dbus_client.status(): {e}"))?;
-                    // getting default app index if set otherwise -1 (to do 1 after the increment)
-                    let default_index = status
-                        .applications
-                        .iter()
-                        .enumerate()
-                        .find(|(_, app)| app.is_default)
-                        .map(|(index, _)| index as i64)
-                        .unwrap_or(-1);
-                    let mut next_index = default_index + 1;
-                    if next_index >= status.applications.len() as i64 {
-                        next_index = -1; // if we are at the end, we unset the default
-                    }
-                    // setting the next default app index
+                } else if set_default_next || set_default_prev {
+                    // done by the daemon so concurrent calls do not race
+                    let _ = dbus_client
+                        .cycle_default(set_default_next)
+                        .map_err(|e| format_err!("on dbus_client.cycle_default(): {e}"))?;
+                    return Ok(());
+                } else if reload {
                     let _ = dbus_client
-                        .set_default(next_index)
-                        .map_err(|e| format_err!("on dbus_client.set_default_next(): {e}"))?;
+                        .reload()
+                        .map_err(|e| format_err!("on dbus_client.reload(): {e}"))?;
                     return Ok(());
                 } else if waybar {
                     let status = dbus_client
@@ -120,7 +140,12 @@ fn run() -> Result<()> {
                         class: String,
                         alt: String,
                     }
-                    let default_application = status.applications.iter().find(|app| app.is_default);
+                    // the default set on the daemon, or the one of the default rules right now
+                    let default_application = status
+                        .applications
+                        .iter()
+                        .find(|app| app.is_default)
+                        .or_else(|| status.applications.iter().find(|app| app.is_default_rule));
                     let application_name = default_application
                         .map_or_else(|| "Select".to_string(), |app| app.name.clone());
                     let css_class = default_application
@@ -144,37 +169,133 @@ fn run() -> Result<()> {
                 ));
             }
         }
+        Some(cli::Commands::Init) => {
+            let config_path =
+                config::Config::path().map_err(|e| format_err!("on Config::path(): {e}"))?;
+            init::init_config(&config_path).map_err(|e| format_err!("on init_config(): {e}"))?;
+            info!("config file written: {}", config_path.display());
+            return Ok(());
+        }
+        Some(cli::Commands::Config {
+            command: cli::ConfigCommands::Check { strict },
+        }) => {
+            let config_path =
+                config::Config::path().map_err(|e| format_err!("on Config::path(): {e}"))?;
+            let problems = check::check_config(&config_path)
+                .map_err(|e| format_err!("on check_config(): {e}"))?;
+            for problem in &problems {
+                match problem.line {
+                    Some(line) => println!(
+                        "{}:{}: {}: {}",
+                        config_path.display(),
+                        line,
+                        problem.severity,
+                        problem.message
+                    ),
+                    None => println!(
+                        "{}: {}: {}",
+                        config_path.display(),
+                        problem.severity,
+                        problem.message
+                    ),
+                }
+            }
+            let errors = problems
+                .iter()
+                .filter(|p| strict || p.severity == check::Severity::Error)
+                .count();
+            if errors > 0 {
+                return Err(format_err!(
+                    "config check failed with {} problem(s)",
+                    errors
+                ));
+            }
+            return Ok(());
+        }
+        Some(cli::Commands::Explain { uri, json }) => {
+            let cfg = config::Config::read().map_err(|e| format_err!("on Config::read(): {e}"))?;
+            // the daemon default application only applies if the daemon is running
+            let daemon_status = dbus::DBUSClient::new()
+                .ok()
+                .and_then(|dbus_client| dbus_client.status().ok());
+            let daemon_default = daemon_status
+                .as_ref()
+                .and_then(|status| status.applications.iter().find(|app| app.is_default))
+                .map(|app| app.id.clone());
+            // paths are explained as the `file://` uris they are opened as
+            let uri = rewrite::uri_from_arg(&uri);
+            let explanation = explain::explain(
+                &cfg,
+                &uri,
+                daemon_status.is_some(),
+                daemon_default,
+                SystemClock.now(),
+            );
+            if json {
+                serde_json::to_writer(std::io::stdout(), &explanation)
+                    .expect("failed to write explain command output");
+            } else {
+                println!("{explanation}");
+            }
+            return Ok(());
+        }
+        Some(cli::Commands::Install) => {
+            let paths = install::InstallPaths::from_xdg()
+                .map_err(|e| format_err!("on InstallPaths::from_xdg(): {e}"))?;
+            let exec = env::current_exe().map_err(|e| format_err!("on current_exe(): {e}"))?;
+            install::install(&paths, &exec).map_err(|e| format_err!("on install(): {e}"))?;
+            return Ok(());
+        }
+        Some(cli::Commands::Uninstall) => {
+            let paths = install::InstallPaths::from_xdg()
+                .map_err(|e| format_err!("on InstallPaths::from_xdg(): {e}"))?;
+            install::uninstall(&paths).map_err(|e| format_err!("on uninstall(): {e}"))?;
+            return Ok(());
+        }
         None => {
             // run the UI
             warn!(
-                "no command provided, running in client mode: uri={:?}",
-                cli.uri
+                "no command provided, running in client mode: uris={:?}",
+                cli.uris
             );
         }
     }
 
     // if no daemon mode, we try to connect to it
     // and if we fail we fallback with local resolution (and eventually start the UI onf fallback)
-    if !daemon_mode {
-        if let Some(uri) = &cli.uri {
-            if let Ok(dbus_client) = dbus::DBUSClient::new() {
-                debug!("connected to dbus in client mode");
-                match dbus_client.open(uri) {
-                    Ok(outputs) => {
-                        info!("open command executed successfully: {:?}", outputs);
-                        std::process::exit(0);
-                    }
-                    Err(e) => {
-                        // we are not exiting here, we will fallback to standalone mode
-                        error!(
-                            "failed to execute open command: {}, fallbacking to standalone mode",
-                            e
-                        );
-                    }
+    if !daemon_mode && !cli.uris.is_empty() {
+        if let Ok(dbus_client) = dbus::DBUSClient::new() {
+            debug!("connected to dbus in client mode");
+            let result = match cli.uris.as_slice() {
+                [uri] => dbus_client.open(uri).map(|outputs| {
+                    info!("open command executed successfully: {:?}", outputs);
+                    Vec::new()
+                }),
+                uris => dbus_client.open_many(uris).map(|outputs| {
+                    info!("open_many command executed: {:?}", outputs);
+                    outputs.failed
+                }),
+            };
+            match result {
+                Ok(failed) if failed.is_empty() => std::process::exit(0),
+                Ok(failed) => {
+                    // the other uris were handled by the daemon, only these ones are retried
+                    error!(
+                        "failed to launch {} uri(s), fallbacking to standalone mode",
+                        failed.len()
+                    );
+                    cli.uris = failed;
+                }
+                Err(e) => {
+                    // we are not exiting here, we will fallback to standalone mode
+                    error!(
+                        "failed to execute open command: {}, fallbacking to standalone mode",
+                        e
+                    );
                 }
-            } else {
-                warn!("failed to create dbus client, using standalone mode");
             }
+        } else {
+            warn!("failed to create dbus client, using standalone mode");
         }
     }
 
@@ -185,33 +306,33 @@ fn run() -> Result<()> {
 
     let (jh_dekstop_files, desktop_files_tx) = run_desktop_file_opener(cfg.clone());
 
-    // if we have an uri maybe we can open it?
-    let resolved = if let Some(uri) = &cli.uri {
-        let mut found = false;
-        for desktop_file in &cfg.desktop_files {
-            if desktop_file.match_uri(uri) {
-                debug!("found matching desktop file: {}", desktop_file.id);
-                // we have a matching desktop file, we can open the url
-                if let Err(e) = desktop_files_tx.send(
-                    desktop_files::DesktopFileOpenerCommand::Open(desktop_files::OpenParams {
-                        uris: vec![uri.clone()],
-                        desktop_file_id: desktop_file.id.clone(),
-                    }),
-                ) {
-                    error!("failed to send open command: {}", e);
-                    std::process::exit(1);
-                }
-                found = true;
-                break;
-            }
+    // if we have uris maybe we can open them?
+    // the rewritten uris are the ones matched and launched
+    let uris = cli
+        .uris
+        .iter()
+        .map(|uri| rewrite::rewrite_uri(&cfg.rewrite, uri))
+        .collect::<Vec<_>>();
+    // no daemon here, so no runtime default application
+    let (groups, pending) = cfg.route(&uris, None, SystemClock.now());
+    for group in groups {
+        // we have a matching desktop file, we can open the urls
+        if let Err(e) = desktop_files_tx.send(desktop_files::DesktopFileOpenerCommand::Open(
+            desktop_files::OpenParams {
+                uris: group.uris,
+                desktop_file_id: group.desktop_file_id,
+                options: group.options,
+                reply: None,
+            },
+        )) {
+            error!("failed to send open command: {}", e);
+            std::process::exit(1);
         }
-        found
-    } else {
-        false
-    };
+    }
+    let resolved = !uris.is_empty() && pending.is_empty();
 
     let (shutdown_signal_tx, shutdown_signal_rx) = mpsc::channel::<()>();
-    let (ui_tx, ui_rx) = async_channel::bounded::<String>(1);
+    let (ui_tx, ui_rx) = async_channel::bounded::<ui::UiCommand>(1);
 
     // register dbus in daemon mode
     let desktop_files_tx_clone = desktop_files_tx.clone();
@@ -243,7 +364,7 @@ fn run() -> Result<()> {
             desktop_files_tx_clone,
             ui_rx,
             daemon_mode,
-            cli.uri,
+            pending,
         );
 
         info!("running application: {}", application_id);
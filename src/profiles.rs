@@ -0,0 +1,214 @@
+use anyhow::{Result, format_err};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::PathBuf};
+use xdg::BaseDirectories;
+
+/// Browsers whose profiles can be listed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfilesKind {
+    Firefox,
+    Chromium,
+    GoogleChrome,
+}
+
+/// A browser profile, offered as a choice of its own.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    /// what rules reference after the `:`
+    /// the profile name for Firefox, the profile directory for Chromium (eg: `Profile 2`)
+    pub id: String,
+    /// displayed name
+    pub name: String,
+    /// avatar color, eg: `#1a73e8`
+    pub color: Option<String>,
+}
+
+impl ProfilesKind {
+    /// Where the browser keeps `profiles.ini` or `Local State`.
+    pub fn default_dir(&self) -> Option<PathBuf> {
+        let config_home = BaseDirectories::new().get_config_home();
+        match self {
+            ProfilesKind::Firefox => dirs::home_dir().map(|home| home.join(".mozilla/firefox")),
+            ProfilesKind::Chromium => config_home.map(|config| config.join("chromium")),
+            ProfilesKind::GoogleChrome => config_home.map(|config| config.join("google-chrome")),
+        }
+    }
+
+    /// Arguments selecting the profile on launch.
+    pub fn args(&self, profile_id: &str) -> Vec<String> {
+        match self {
+            ProfilesKind::Firefox => vec!["-P".to_string(), profile_id.to_string()],
+            ProfilesKind::Chromium | ProfilesKind::GoogleChrome => {
+                vec![format!("--profile-directory={}", profile_id)]
+            }
+        }
+    }
+}
+
+/// Lists the profiles in `dir`, or in the browser default directory.
+pub fn discover_profiles(kind: ProfilesKind, dir: Option<&str>) -> Result<Vec<Profile>> {
+    let dir = match dir {
+        Some(dir) => expand_home(dir),
+        None => kind
+            .default_dir()
+            .ok_or_else(|| format_err!("no default profiles directory for {:?}", kind))?,
+    };
+    match kind {
+        ProfilesKind::Firefox => {
+            let path = dir.join("profiles.ini");
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format_err!("failed to read {}: {}", path.display(), e))?;
+            Ok(parse_firefox_profiles(&content))
+        }
+        ProfilesKind::Chromium | ProfilesKind::GoogleChrome => {
+            let path = dir.join("Local State");
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format_err!("failed to read {}: {}", path.display(), e))?;
+            parse_chromium_local_state(&content)
+        }
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(end), Some(home)) => home.join(end),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `[ProfileN]` sections of Firefox `profiles.ini`, in file order.
+fn parse_firefox_profiles(content: &str) -> Vec<Profile> {
+    let mut profiles = Vec::new();
+    let mut in_profile = false;
+    for line in content.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = section.starts_with("Profile");
+            continue;
+        }
+        if in_profile && let Some(name) = line.strip_prefix("Name=") {
+            profiles.push(Profile {
+                id: name.to_string(),
+                name: name.to_string(),
+                // Firefox does not store a color in `profiles.ini`
+                color: None,
+            });
+        }
+    }
+    profiles
+}
+
+#[derive(Deserialize)]
+struct LocalState {
+    profile: LocalStateProfiles,
+}
+
+#[derive(Deserialize)]
+struct LocalStateProfiles {
+    info_cache: HashMap<String, LocalStateProfile>,
+    /// order of the profiles in the Chromium profile picker
+    #[serde(default)]
+    profiles_order: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LocalStateProfile {
+    name: String,
+    profile_highlight_color: Option<i64>,
+    default_avatar_fill_color: Option<i64>,
+}
+
+/// Profiles of Chromium `Local State`, in the profile picker order.
+fn parse_chromium_local_state(content: &str) -> Result<Vec<Profile>> {
+    let local_state: LocalState = serde_json::from_str(content)?;
+    let LocalStateProfiles {
+        mut info_cache,
+        profiles_order,
+    } = local_state.profile;
+
+    let mut directories = profiles_order
+        .into_iter()
+        .filter(|dir| info_cache.contains_key(dir))
+        .collect::<Vec<_>>();
+    let mut remaining = info_cache
+        .keys()
+        .filter(|dir| !directories.contains(dir))
+        .cloned()
+        .collect::<Vec<_>>();
+    remaining.sort();
+    directories.extend(remaining);
+
+    Ok(directories
+        .into_iter()
+        .filter_map(|dir| {
+            let profile = info_cache.remove(&dir)?;
+            Some(Profile {
+                color: profile
+                    .profile_highlight_color
+                    .or(profile.default_avatar_fill_color)
+                    // colors are stored as signed ARGB integers
+                    .map(|color| format!("#{:06x}", color & 0xffffff)),
+                name: profile.name,
+                id: dir,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(browser: &str) -> String {
+        format!(
+            "{}/tests/fixtures/profiles/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            browser
+        )
+    }
+
+    #[test]
+    fn discover_firefox_profiles() {
+        let profiles = discover_profiles(ProfilesKind::Firefox, Some(&fixture("firefox"))).unwrap();
+        let names = profiles
+            .iter()
+            .map(|profile| (profile.id.as_str(), profile.name.as_str()))
+            .collect::<Vec<_>>();
+        // `[Install…]` and `[General]` are not profiles
+        assert_eq!(
+            names,
+            vec![("work", "work"), ("default-release", "default-release")]
+        );
+        assert!(profiles.iter().all(|profile| profile.color.is_none()));
+    }
+
+    #[test]
+    fn discover_chromium_profiles() {
+        let profiles =
+            discover_profiles(ProfilesKind::Chromium, Some(&fixture("chromium"))).unwrap();
+        let profiles = profiles
+            .iter()
+            .map(|profile| {
+                (
+                    profile.id.as_str(),
+                    profile.name.as_str(),
+                    profile.color.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // `profiles_order` first, without the unknown directories, then the others
+        assert_eq!(
+            profiles,
+            vec![
+                ("Profile 2", "Work", Some("#1a73e8")),
+                ("Default", "Perso", Some("#e8710a")),
+                ("Profile 3", "Guest", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn discover_profiles_without_file() {
+        assert!(discover_profiles(ProfilesKind::Firefox, Some(&fixture("chromium"))).is_err());
+    }
+}
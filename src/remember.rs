@@ -0,0 +1,188 @@
+use anyhow::{Result, format_err};
+use std::{fs, path::Path};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, value};
+use tracing::info;
+
+use crate::config::{Choice, Config};
+
+/// What a remembered choice matches.
+#[derive(Clone, Debug)]
+pub enum Remember {
+    /// every uri of this host
+    Host(String),
+    /// every uri starting with this one
+    Prefix(String),
+}
+
+/// Reference to a choice, written the way rules reference applications.
+pub fn choice_reference(cfg: &Config, choice: &Choice) -> String {
+    let Some(desktop_file) = cfg.get_desktop_file(&choice.desktop_file_id) else {
+        return choice.id.clone();
+    };
+    let application = desktop_file
+        .name
+        .clone()
+        .or_else(|| desktop_file.alias.clone())
+        .unwrap_or_else(|| desktop_file.id.clone());
+    match choice
+        .id
+        .strip_prefix(&desktop_file.id)
+        .and_then(|id| id.strip_prefix(':'))
+    {
+        Some(variant) => format!("{}:{}", application, variant),
+        None => application,
+    }
+}
+
+/// Adds a rule for `application` to the config file, keeping its comments and formatting.
+pub fn remember(config_path: &Path, application: &str, remember: &Remember) -> Result<()> {
+    let content = fs::read_to_string(config_path)?;
+    let content = append_rule(&content, application, remember)?;
+    // never write a config the daemon would refuse
+    Config::parse(&content)?;
+    fs::write(config_path, content)?;
+    info!("rule added for '{}': {:?}", application, remember);
+    Ok(())
+}
+
+/// The rule is added after the other rules, but before a default rule without schedule
+/// since the rules after it are never evaluated.
+fn append_rule(content: &str, application: &str, remember: &Remember) -> Result<String> {
+    let mut document: DocumentMut = content.parse()?;
+
+    let mut rule = Table::new();
+    let (key, matcher) = match remember {
+        Remember::Host(host) => ("hosts", host),
+        Remember::Prefix(prefix) => ("prefixes", prefix),
+    };
+    rule.insert(key, value(Array::from_iter([matcher.as_str()])));
+    rule.insert("application", value(application));
+
+    let rules = document
+        .entry("rule")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| format_err!("`rule` is not an array of tables"))?;
+    let mut tables = std::mem::take(rules).into_iter().collect::<Vec<_>>();
+    let index = tables
+        .iter()
+        .position(is_terminal_default)
+        .unwrap_or(tables.len());
+    // tables are written by position, taking the one of the default rule puts the new one before it
+    if let Some(position) = tables.get(index).and_then(Table::position) {
+        rule.set_position(position);
+    }
+    tables.insert(index, rule);
+    for table in tables {
+        rules.push(table);
+    }
+
+    Ok(document.to_string())
+}
+
+fn is_terminal_default(table: &Table) -> bool {
+    table.contains_key("default")
+        && !["days", "from", "to"]
+            .iter()
+            .any(|key| table.contains_key(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_rule_before_the_default_rule() {
+        let content = r#"[[rule]]
+hosts = ["github.com"]
+application = "Work"
+
+[[rule]]
+default = "Perso"
+
+[[application]]
+desktop_id = "firefox.desktop"
+name = "Perso"
+"#;
+        let content = append_rule(content, "Work", &Remember::Host("example.com".into())).unwrap();
+        assert_eq!(
+            content,
+            r#"[[rule]]
+hosts = ["github.com"]
+application = "Work"
+
+[[rule]]
+hosts = ["example.com"]
+application = "Work"
+
+[[rule]]
+default = "Perso"
+
+[[application]]
+desktop_id = "firefox.desktop"
+name = "Perso"
+"#
+        );
+    }
+
+    #[test]
+    fn append_rule_to_a_legacy_config() {
+        let content = r#"[[application]]
+desktop_id = "firefox.desktop"
+name = "Perso"
+prefixes = ["https://"]
+"#;
+        let content = append_rule(
+            content,
+            "Perso:work",
+            &Remember::Prefix("https://example.com/".into()),
+        )
+        .unwrap();
+        assert_eq!(
+            content,
+            r#"[[application]]
+desktop_id = "firefox.desktop"
+name = "Perso"
+prefixes = ["https://"]
+
+[[rule]]
+prefixes = ["https://example.com/"]
+application = "Perso:work"
+"#
+        );
+    }
+
+    #[test]
+    fn append_rule_keeps_comments() {
+        let content = r#"# my browsers
+
+# work stuff
+[[rule]]
+hosts = ["github.com"] # and not gitlab
+application = "Work"
+
+# everything else
+[[rule]]
+default = "Perso"
+"#;
+        let content = append_rule(content, "Work", &Remember::Host("example.com".into())).unwrap();
+        assert_eq!(
+            content,
+            r#"# my browsers
+
+# work stuff
+[[rule]]
+hosts = ["github.com"] # and not gitlab
+application = "Work"
+
+[[rule]]
+hosts = ["example.com"]
+application = "Work"
+
+# everything else
+[[rule]]
+default = "Perso"
+"#
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+use tracing::debug;
+use url::Url;
+
+use crate::config::RewriteConfig;
+
+/// Redirectors are unwrapped again and again, up to this limit.
+const MAX_UNWRAP: usize = 5;
+
+/// Command line arguments are uris or paths, existing paths become absolute `file://` uris.
+pub fn uri_from_arg(arg: &str) -> String {
+    if Url::parse(arg).is_ok() {
+        return arg.to_string();
+    }
+    Path::new(arg)
+        .canonicalize()
+        .ok()
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map_or_else(|| arg.to_string(), |url| url.to_string())
+}
+
+/// Rewrites an uri before matching it: unwraps redirectors, strips tracking
+/// query parameters, then applies the user substitutions.
+pub fn rewrite_uri(cfg: &RewriteConfig, uri: &str) -> String {
+    let mut uri = uri.to_string();
+
+    if cfg.unwrap_redirects {
+        for _ in 0..MAX_UNWRAP {
+            let Some(target) = unwrap_redirect(&uri) else {
+                break;
+            };
+            debug!("unwrapped redirect: {} -> {}", uri, target);
+            uri = target;
+        }
+    }
+
+    if !cfg.strip_params.is_empty()
+        && let Some(stripped) = strip_params(&uri, &cfg.strip_params)
+    {
+        debug!("stripped query parameters: {} -> {}", uri, stripped);
+        uri = stripped;
+    }
+
+    for substitution in &cfg.substitutions {
+        let Some(regex) = &substitution.compiled else {
+            continue;
+        };
+        let replaced = regex.replace_all(&uri, substitution.replace.as_str());
+        if replaced != uri {
+            debug!(
+                "substitution '{}': {} -> {}",
+                substitution.regexp, uri, replaced
+            );
+            uri = replaced.into_owned();
+        }
+    }
+
+    uri
+}
+
+/// Target of a known redirector, only if it is an http(s) url.
+fn unwrap_redirect(uri: &str) -> Option<String> {
+    let url = Url::parse(uri).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let path = url.path();
+
+    let params: &[&str] = match (host.as_str(), path) {
+        (host, "/url") if host == "google.com" || host.starts_with("www.google.") => &["q", "url"],
+        (host, _) if host.ends_with(".safelinks.protection.outlook.com") => &["url"],
+        ("slack-redir.net", "/link") => &["url"],
+        ("l.facebook.com" | "lm.facebook.com" | "l.messenger.com", "/l.php") => &["u"],
+        ("www.youtube.com", "/redirect") => &["q"],
+        _ => return None,
+    };
+
+    params.iter().find_map(|param| {
+        url.query_pairs()
+            .find(|(key, _)| key == param)
+            .map(|(_, value)| value.into_owned())
+            .filter(|value| {
+                Url::parse(value).is_ok_and(|target| matches!(target.scheme(), "http" | "https"))
+            })
+    })
+}
+
+/// Returns `None` if no parameter was removed, to keep the uri untouched.
+fn strip_params(uri: &str, patterns: &[String]) -> Option<String> {
+    let mut url = Url::parse(uri).ok()?;
+    let query = url.query()?;
+
+    // the kept parameters are not decoded, so they stay byte for byte
+    let pieces: Vec<&str> = query.split('&').collect();
+    let kept: Vec<&str> = pieces
+        .iter()
+        .copied()
+        .filter(|piece| {
+            let key = url::form_urlencoded::parse(piece.as_bytes())
+                .next()
+                .map(|(key, _)| key.into_owned())
+                .unwrap_or_default();
+            !patterns.iter().any(|pattern| match_param(pattern, &key))
+        })
+        .collect();
+    if kept.len() == pieces.len() {
+        return None;
+    }
+
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.set_query(Some(&kept.join("&")));
+    }
+    Some(url.to_string())
+}
+
+/// `utm_*` matches every parameter starting with `utm_`.
+fn match_param(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_params_keeps_other_params_as_they_are() {
+        let patterns = vec!["utm_*".to_string()];
+        assert_eq!(
+            strip_params(
+                "https://a.com/?a=hello%20world&utm_source=x&sig=ab%2Fc+d",
+                &patterns
+            ),
+            Some("https://a.com/?a=hello%20world&sig=ab%2Fc+d".to_string())
+        );
+        assert_eq!(
+            strip_params("https://a.com/?utm_source=x#top", &patterns),
+            Some("https://a.com/#top".to_string())
+        );
+        assert_eq!(strip_params("https://a.com/?a=1", &patterns), None);
+    }
+}
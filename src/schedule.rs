@@ -0,0 +1,146 @@
+use anyhow::{Result, format_err};
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+
+/// Gives the current time, so schedules can be evaluated at any time.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// Local time of the system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// A time in June 2025, the 2nd is a Monday and the 7th a Saturday,
+/// so tests do not depend on when they run.
+#[cfg(test)]
+pub fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(2025, 6, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+/// When a default rule applies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    /// empty means every day
+    pub days: Vec<Weekday>,
+    /// included
+    pub from: Option<NaiveTime>,
+    /// excluded, if before `from` the range goes over midnight
+    pub to: Option<NaiveTime>,
+}
+
+impl Schedule {
+    /// `days` are `mon`, `monday`..., or `weekdays` and `weekend`, times are `HH:MM`.
+    pub fn parse(days: &[String], from: Option<&str>, to: Option<&str>) -> Result<Self> {
+        let mut parsed_days = Vec::new();
+        for day in days {
+            match day.to_ascii_lowercase().as_str() {
+                "weekdays" => parsed_days.extend([
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ]),
+                "weekend" => parsed_days.extend([Weekday::Sat, Weekday::Sun]),
+                _ => parsed_days.push(
+                    day.parse::<Weekday>()
+                        .map_err(|_| format_err!("invalid day '{}'", day))?,
+                ),
+            }
+        }
+        Ok(Self {
+            days: parsed_days,
+            from: from.map(parse_time).transpose()?,
+            to: to.map(parse_time).transpose()?,
+        })
+    }
+
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        if !self.days.is_empty() && !self.days.contains(&now.weekday()) {
+            return false;
+        }
+        let time = now.time();
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if to < from => time >= from || time < to,
+            (from, to) => from.is_none_or(|from| time >= from) && to.is_none_or(|to| time < to),
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| format_err!("invalid time '{}', expected HH:MM", time))
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = if self.days.is_empty() {
+            "every day".to_string()
+        } else {
+            self.days
+                .iter()
+                .map(|day| day.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(f, "{}", days)?;
+        if let Some(from) = self.from {
+            write!(f, " from {}", from.format("%H:%M"))?;
+        }
+        if let Some(to) = self.to {
+            write!(f, " to {}", to.format("%H:%M"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(days: &[&str], from: Option<&str>, to: Option<&str>) -> Schedule {
+        let days = days.iter().map(|day| day.to_string()).collect::<Vec<_>>();
+        Schedule::parse(&days, from, to).unwrap()
+    }
+
+    #[test]
+    fn weekdays() {
+        let schedule = schedule(&["weekdays"], None, None);
+        assert!(schedule.is_active(at(2, 12, 0)));
+        assert!(!schedule.is_active(at(7, 12, 0)));
+    }
+
+    #[test]
+    fn to_is_excluded() {
+        let schedule = schedule(&[], Some("09:00"), Some("18:00"));
+        assert!(!schedule.is_active(at(2, 8, 59)));
+        assert!(schedule.is_active(at(2, 9, 0)));
+        assert!(schedule.is_active(at(2, 17, 59)));
+        assert!(!schedule.is_active(at(2, 18, 0)));
+    }
+
+    #[test]
+    fn range_over_midnight() {
+        let schedule = schedule(&["mon"], Some("22:00"), Some("06:00"));
+        assert!(schedule.is_active(at(2, 23, 0)));
+        assert!(schedule.is_active(at(2, 5, 0)));
+        assert!(!schedule.is_active(at(2, 12, 0)));
+        // the day is the one of the current time
+        assert!(!schedule.is_active(at(3, 1, 0)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Schedule::parse(&["someday".to_string()], None, None).is_err());
+        assert!(Schedule::parse(&[], Some("9h"), None).is_err());
+        assert!(Schedule::parse(&[], None, Some("25:00")).is_err());
+    }
+}
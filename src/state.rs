@@ -0,0 +1,53 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+use tracing::{debug, info, warn};
+use xdg::BaseDirectories;
+
+/// Runtime state kept across daemon restarts.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    /// choice id set with `--set-default`
+    pub default_application_id: Option<String>,
+    /// choice id of the last application opened from the UI
+    pub last_application_id: Option<String>,
+}
+
+impl State {
+    pub fn path() -> Result<PathBuf> {
+        let xdg_dirs = BaseDirectories::with_prefix(env!("CARGO_PKG_NAME"));
+        Ok(xdg_dirs.place_state_file("state.toml")?)
+    }
+
+    /// A missing or invalid state file is an empty state.
+    pub fn read() -> State {
+        let path = match State::path() {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("unable to find the state file: {}", e);
+                return State::default();
+            }
+        };
+        info!("state path: {}", path.display());
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return State::default(),
+            Err(e) => {
+                warn!("failed to read state file: {}", e);
+                return State::default();
+            }
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("invalid state file, ignoring it: {}", e);
+            State::default()
+        })
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let path = State::path()?;
+        fs::write(&path, toml::to_string(self)?)?;
+        debug!("state written: {}", path.display());
+        Ok(())
+    }
+}
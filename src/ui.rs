@@ -1,151 +1,89 @@
-use crate::config::{Config, read_css_file};
-use crate::desktop_files::{DesktopFileOpenerCommand, OpenParams, resolve_desktop_files};
+use crate::config::{Config, LaunchOptions, read_css_file};
+use crate::dbus::DBUSClient;
+use crate::desktop_files::{
+    DesktopFileOpenerCommand, OpenParams, private_action, resolve_choices, resolve_desktop_files,
+};
+use crate::fuzzy::fuzzy_match;
+use crate::remember::{Remember, choice_reference, remember};
+use crate::state::State;
 use gtk4::gio::{self};
-use gtk4::{self as gtk, Align, Box, Image, Label, ListBox, Orientation, SelectionMode, Window};
-use gtk4::{Application, Button};
-use gtk4::{glib, prelude::*};
+use gtk4::{
+    self as gtk, Align, Box, Image, Label, ListBox, ListBoxRow, Orientation, SelectionMode, Window,
+};
+use gtk4::{Application, Button, CheckButton, Expander};
+use gtk4::{gdk::ModifierType, glib, prelude::*};
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use tracing::{debug, error, info, warn};
+use url::Url;
+
+pub enum UiCommand {
+    /// Show the window to choose an application for the given uris.
+    Show(Vec<String>),
+
+    /// Replace the config, rebuild the list and reload the CSS.
+    Reload(Config),
+}
 
 pub fn start_ui(
     application_id: &str,
     application_name: &str,
     cfg: &Config,
     desktop_files_tx: Sender<DesktopFileOpenerCommand>,
-    ui_rx: async_channel::Receiver<String>,
+    ui_rx: async_channel::Receiver<UiCommand>,
     daemon_mode: bool,
-    uri: Option<String>,
+    uris: Vec<String>,
 ) -> Application {
     let application = Application::builder()
         .application_id(application_id)
         .flags(gio::ApplicationFlags::HANDLES_OPEN | gio::ApplicationFlags::NON_UNIQUE)
         .build();
 
-    let shared_files: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(uri));
+    let shared_files: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(uris));
     let shared_files_clone_open = Rc::clone(&shared_files);
 
     // connect to the 'open' signal, which is triggered when the application is launched with URIs/files.
     application.connect_open(move |app, _, _| {
         // just to avoid a GIO critical and force activation
-        // the args are handled via clap in the main.rs
+        // the args are handled via clap in the main.rs, paths are turned into `file://` uris there
         app.activate();
     });
 
     let application_name_clone = application_name.to_string();
-    let cfg_clone = cfg.clone();
+    let shared_cfg = Rc::new(RefCell::new(cfg.clone()));
+    let shared_cfg_clone = Rc::clone(&shared_cfg);
+    let shared_picker: Rc<RefCell<Option<Picker>>> = Rc::new(RefCell::new(None));
+    let shared_picker_clone = Rc::clone(&shared_picker);
+    let css_provider: Rc<RefCell<Option<gtk::CssProvider>>> = Rc::new(RefCell::new(None));
+    let css_provider_clone = Rc::clone(&css_provider);
     let desktop_files_clone = desktop_files_tx.clone();
+    let shared_files_clone_activate = Rc::clone(&shared_files);
     application.connect_activate(move |app| {
         debug!("app activated");
 
-         // css
-         let display = &gtk::gdk::Display::default().expect("could not connect to a display.");
-         match read_css_file() {
-            Err(e) => {
-                warn!("failed to read css file: {}", e);
-            }
-            Ok(css_content) => {
-                let provider = gtk::CssProvider::new();
-                provider.load_from_data(&css_content);
-                gtk::style_context_add_provider_for_display(
-                    display,
-                    &provider,
-                    gtk::STYLE_PROVIDER_PRIORITY_USER,
-                );
-            }
-        };
+        // css
+        let display = &gtk::gdk::Display::default().expect("could not connect to a display.");
+        let provider = gtk::CssProvider::new();
+        load_css(&provider);
+        gtk::style_context_add_provider_for_display(
+            display,
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_USER,
+        );
+        *css_provider_clone.borrow_mut() = Some(provider);
 
         debug!("CSS is loaded");
 
-        let list_box = ListBox::builder()
-            .selection_mode(SelectionMode::None)
-            .css_classes(vec![String::from("list")])
-            .build();
-
-        let desktop_files = resolve_desktop_files(&cfg_clone);
-        let desktop_files_len = desktop_files.len();
-        for (idx, desktop_file_config) in cfg_clone.desktop_files.iter().enumerate(){
-            let Some(desktop_file) = desktop_files.get(&desktop_file_config.id) else {
-                warn!("no desktop file found for id: {}", desktop_file_config.id);
-                continue;
-            };
-            let mut button_css_classes = vec![String::from("application")];
-            if idx == 0 {
-                button_css_classes.push("first".into());
-            } else if idx == desktop_files_len - 1 {
-                button_css_classes.push("last".into());
-            }
-            let button = Button::builder()
-                .css_classes(button_css_classes)
-                .label(desktop_file_config.alias.as_ref().map_or(desktop_file.name(), |alias| alias.into()))
-                .build();
-
-            let button_box = Box::builder()
-                .orientation(Orientation::Horizontal)
-                .spacing(12)
-                .css_classes(vec![String::from("box")])
-                .build();
-            button.set_child(Some(&button_box));
-
-            if let Some(icon) = desktop_file.icon() {
-                let icon_image = Image::builder()
-                .gicon(&icon)
-                .css_classes(vec![String::from("icon")])
-                    .pixel_size(48)
-                    .margin_end(12)
-                    .build();
-                button_box.append(&icon_image);
-            }
-
-            button_box.append(&Label::builder()
-                .label(desktop_file_config.alias.as_ref().map_or(desktop_file.name(), |alias| alias.into()))
-                .css_classes(vec![String::from("label")])
-                .build());
-
-            let desktop_id_for_closure = desktop_file_config.id.clone();
-            let desktop_files_tx_for_closure = desktop_files_clone.clone();
-            let shared_uri_clone_active = Rc::clone(&shared_files);
-            let app_for_closure = app.clone();
-            button.connect_clicked(move |_| {
-                let uri = shared_uri_clone_active.borrow().clone().unwrap_or_default();
-                if let Err(e) = desktop_files_tx_for_closure.send(DesktopFileOpenerCommand::Open(
-                    OpenParams {
-                        uris: vec![uri],
-                        desktop_file_id: desktop_id_for_closure.clone(),
-                    },
-                )) {
-                    error!("failed to send command to desktop file opener: {}", e);
-                }
-                info!("after sending command, quitting the app");
-                if daemon_mode {
-                app_for_closure.windows()
-                    .iter()
-                    .for_each(|window| window.hide());
-                } else {
-                    app_for_closure.quit();
-                }
-            });
-            list_box.append(&button);
-        }
-
-        let content = Box::builder()
-            .orientation(Orientation::Vertical)
-            .css_classes(vec!["main-box".to_string()])
-            .build();
-
-        if desktop_files_len == 0 {
-            let label = Label::builder()
-                .label("No desktop entries found or processed from the list.\nPlease check the paths in `DESKTOP_FILES` constant.")
-                .halign(Align::Center)
-                .valign(Align::Center)
-                .wrap(true)
-                .build();
-            content.append(&label);
-        } else {
-            content.append(&list_box);
-        }
-
+        let (content, picker) = build_content(
+            app,
+            &shared_cfg_clone.borrow(),
+            &desktop_files_clone,
+            &shared_files_clone_activate,
+            daemon_mode,
+        );
         let window = Window::builder()
             .application(app)
             .title(&application_name_clone)
@@ -156,15 +94,26 @@ pub fn start_ui(
             .css_classes(vec!["main-window"])
             .child(&content)
             .build();
+        // the uri entry would take the focus first, and the digits with it
+        focus_selected_row(&window, &picker.list_box);
+        *shared_picker_clone.borrow_mut() = Some(picker);
 
         debug!("window is built");
 
         // mapping keyboard shortcuts
         let keys_controller = gtk::EventControllerKey::new();
-        let list_box_clone = list_box.clone();
+        let picker_clone = Rc::clone(&shared_picker_clone);
         let app_clone = app.clone();
-        keys_controller.connect_key_pressed(move |_, keyval, _, _| {
+        keys_controller.connect_key_pressed(move |_, keyval, _, modifiers| {
             if keyval == gtk4::gdk::Key::Escape {
+                // the first Escape only clears the search
+                if picker_clone
+                    .borrow_mut()
+                    .as_mut()
+                    .is_some_and(|picker| picker.clear_query())
+                {
+                    return gtk::glib::Propagation::Stop;
+                }
                 if let Some(window) = app_clone.active_window() {
                     if daemon_mode {
                         window.hide();
@@ -178,23 +127,69 @@ pub fn start_ui(
             }
             if let Some(digit) = keyval.to_unicode().and_then(|c| c.to_digit(10)) {
                 // adjust for 0-based indexing (key '1' maps to index 0)
-                let index = digit.saturating_sub(1) as i32;
+                let index = digit.saturating_sub(1) as usize;
 
-                if let Some(row) = list_box_clone.row_at_index(index) {
+                let row = picker_clone
+                    .borrow()
+                    .as_ref()
+                    .and_then(|picker| picker.visible_rows().into_iter().nth(index));
+                if let Some(row) = row {
                     info!("activating row at index: {:?}", row);
-
-                    let Some(widget) = row.child() else {
-                        warn!("no child widget found in row at index: {}", index);
-                        return gtk::glib::Propagation::Stop;
-                    };
-                    if let Some(button) = widget.downcast_ref::<Button>() {
-                        gtk4::prelude::ButtonExt::emit_clicked(button);
+                    click_row(&row);
+                    return gtk::glib::Propagation::Stop;
+                }
+            }
+            // the `key` of an application opens it, with Shift in a private window,
+            // once a search is started every letter goes to it
+            if let Some(key) = keyval.to_lower().to_unicode()
+                && key.is_ascii_alphabetic()
+                && !modifiers.intersects(ModifierType::CONTROL_MASK | ModifierType::ALT_MASK)
+            {
+                let shortcut = picker_clone
+                    .borrow()
+                    .as_ref()
+                    .filter(|picker| picker.query.is_empty())
+                    .and_then(|picker| picker.shortcuts.get(&key).cloned());
+                if let Some(shortcut) = shortcut {
+                    if !modifiers.contains(ModifierType::SHIFT_MASK) {
+                        click_row(&shortcut.row);
+                    } else if let Some(open_private) = &shortcut.open_private {
+                        open_private();
                     } else {
-                        warn!("no button found in row at index: {}", index);
+                        warn!("no private window action for key: {}", key);
                     }
                     return gtk::glib::Propagation::Stop;
                 }
             }
+            // the other letters search the rows
+            if let Some(letter) = keyval.to_unicode()
+                && letter.is_alphabetic()
+                && !modifiers.intersects(ModifierType::CONTROL_MASK | ModifierType::ALT_MASK)
+            {
+                if let Some(picker) = picker_clone.borrow_mut().as_mut() {
+                    picker.push_query(letter);
+                }
+                return gtk::glib::Propagation::Stop;
+            }
+            if keyval == gtk4::gdk::Key::BackSpace
+                && picker_clone
+                    .borrow_mut()
+                    .as_mut()
+                    .is_some_and(|picker| picker.pop_query())
+            {
+                return gtk::glib::Propagation::Stop;
+            }
+            // a focused row handles Enter itself, this is for the rest of the window
+            if keyval == gtk4::gdk::Key::Return || keyval == gtk4::gdk::Key::KP_Enter {
+                let row = picker_clone
+                    .borrow()
+                    .as_ref()
+                    .and_then(|picker| picker.list_box.selected_row());
+                if let Some(row) = row {
+                    click_row(&row);
+                    return gtk::glib::Propagation::Stop;
+                }
+            }
             gtk::glib::Propagation::Proceed
         });
         window.add_controller(keys_controller);
@@ -232,15 +227,39 @@ pub fn start_ui(
     glib::spawn_future_local(async move {
         loop {
             match ui_rx.recv().await {
-                Ok(uri) => {
-                    debug!("received URI from UI: {}", uri);
-                    *shared_files_clone_open.borrow_mut() = Some(uri);
+                Ok(UiCommand::Show(uris)) => {
+                    debug!("received URIs from UI: {:?}", uris);
+                    *shared_files_clone_open.borrow_mut() = uris;
+                    // the pending uris are listed in the content
+                    rebuild_content(
+                        &app_clone,
+                        &shared_cfg.borrow(),
+                        &desktop_files_tx,
+                        &shared_files,
+                        &shared_picker,
+                        daemon_mode,
+                    );
                     if let Some(win) = app_clone.active_window() {
                         win.show();
                     } else {
                         error!("no active window found");
                     }
                 }
+                Ok(UiCommand::Reload(cfg)) => {
+                    info!("reloading UI");
+                    if let Some(provider) = css_provider.borrow().as_ref() {
+                        load_css(provider);
+                    }
+                    *shared_cfg.borrow_mut() = cfg;
+                    rebuild_content(
+                        &app_clone,
+                        &shared_cfg.borrow(),
+                        &desktop_files_tx,
+                        &shared_files,
+                        &shared_picker,
+                        daemon_mode,
+                    );
+                }
                 Err(e) => {
                     error!("error receiving URI from UI: {}", e);
                     break;
@@ -252,3 +271,643 @@ pub fn start_ui(
     debug!("application is initialized and connected to activate signal");
     application
 }
+
+fn load_css(provider: &gtk::CssProvider) {
+    match read_css_file() {
+        Err(e) => {
+            warn!("failed to read css file: {}", e);
+        }
+        Ok(css_content) => {
+            provider.load_from_data(&css_content);
+        }
+    };
+}
+
+/// Replaces the window content, for a new config or new uris.
+fn rebuild_content(
+    app: &Application,
+    cfg: &Config,
+    desktop_files_tx: &Sender<DesktopFileOpenerCommand>,
+    shared_files: &Rc<RefCell<Vec<String>>>,
+    shared_picker: &Rc<RefCell<Option<Picker>>>,
+    daemon_mode: bool,
+) {
+    let Some(win) = app.active_window() else {
+        // not activated yet, the content is built on activation
+        return;
+    };
+    let (content, picker) = build_content(app, cfg, desktop_files_tx, shared_files, daemon_mode);
+    win.set_child(Some(&content));
+    focus_selected_row(&win, &picker.list_box);
+    *shared_picker.borrow_mut() = Some(picker);
+}
+
+fn focus_selected_row(window: &impl IsA<Window>, list_box: &ListBox) {
+    if let Some(row) = list_box.selected_row().or_else(|| list_box.row_at_index(0)) {
+        GtkWindowExt::set_focus(window.as_ref(), Some(&row));
+    }
+}
+
+/// Opens the application of a row.
+fn click_row(row: &ListBoxRow) {
+    match row.child().and_downcast::<Button>() {
+        Some(button) => button.emit_clicked(),
+        None => warn!("no button found in row at index: {}", row.index()),
+    }
+}
+
+/// Rows of the window, with the application keys and the search.
+struct Picker {
+    list_box: ListBox,
+    rows: Vec<PickerRow>,
+    shortcuts: HashMap<char, Shortcut>,
+    /// selected when the search is empty
+    preselected: Option<ListBoxRow>,
+    query: String,
+    query_label: Label,
+    /// score and position of each row, read by the list sort function
+    ranks: Rc<RefCell<HashMap<ListBoxRow, Rank>>>,
+}
+
+/// Search score of a row, best first, then its position in the config.
+type Rank = (Reverse<i64>, usize);
+
+struct PickerRow {
+    row: ListBoxRow,
+    /// displayed name, the matched letters are highlighted in it
+    label: Label,
+    name: String,
+    /// desktop file name and keywords, also searched
+    terms: Vec<String>,
+}
+
+impl Picker {
+    fn push_query(&mut self, letter: char) {
+        self.query.push(letter);
+        self.update();
+    }
+
+    /// Returns false if there was nothing to remove.
+    fn pop_query(&mut self) -> bool {
+        if self.query.pop().is_none() {
+            return false;
+        }
+        self.update();
+        true
+    }
+
+    /// Returns false if there was no search.
+    fn clear_query(&mut self) -> bool {
+        if self.query.is_empty() {
+            return false;
+        }
+        self.query.clear();
+        self.update();
+        true
+    }
+
+    /// Rows in display order, without the filtered ones.
+    fn visible_rows(&self) -> Vec<ListBoxRow> {
+        (0..)
+            .map_while(|index| self.list_box.row_at_index(index))
+            .filter(|row| row.is_visible())
+            .collect()
+    }
+
+    /// Filters and sorts the rows by how well they match the search, the best one is selected.
+    fn update(&self) {
+        {
+            let mut ranks = self.ranks.borrow_mut();
+            for (index, picker_row) in self.rows.iter().enumerate() {
+                let name_match = fuzzy_match(&self.query, &picker_row.name);
+                let score = picker_row
+                    .terms
+                    .iter()
+                    .filter_map(|term| fuzzy_match(&self.query, term))
+                    .chain(name_match.clone())
+                    .map(|term_match| term_match.score)
+                    .max();
+                picker_row.row.set_visible(score.is_some());
+                picker_row.label.set_markup(&highlight(
+                    &picker_row.name,
+                    &name_match
+                        .map(|name_match| name_match.positions)
+                        .unwrap_or_default(),
+                ));
+                ranks.insert(
+                    picker_row.row.clone(),
+                    (Reverse(score.unwrap_or_default()), index),
+                );
+            }
+        }
+        self.list_box.invalidate_sort();
+
+        self.query_label.set_label(&self.query);
+        self.query_label.set_visible(!self.query.is_empty());
+
+        let row = self
+            .preselected
+            .clone()
+            .filter(|_| self.query.is_empty())
+            .or_else(|| self.visible_rows().into_iter().next());
+        self.list_box.select_row(row.as_ref());
+        if let Some(row) = row {
+            row.grab_focus();
+        }
+    }
+}
+
+/// Pango markup of the text, with the chars at `positions` in bold.
+fn highlight(text: &str, positions: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(index, c)| {
+            let c = glib::markup_escape_text(&c.to_string());
+            if positions.contains(&index) {
+                format!("<b>{}</b>", c)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct Shortcut {
+    row: ListBoxRow,
+    /// opens the application private window action, if it has one
+    open_private: Option<Rc<dyn Fn()>>,
+}
+
+fn build_content(
+    app: &Application,
+    cfg: &Config,
+    desktop_files_tx: &Sender<DesktopFileOpenerCommand>,
+    shared_files: &Rc<RefCell<Vec<String>>>,
+    daemon_mode: bool,
+) -> (Box, Picker) {
+    let list_box = ListBox::builder()
+        .selection_mode(SelectionMode::Single)
+        .css_classes(vec![String::from("list")])
+        .build();
+    list_box.connect_row_activated(|_, row| click_row(row));
+    let ranks: Rc<RefCell<HashMap<ListBoxRow, Rank>>> = Rc::default();
+    let ranks_clone = Rc::clone(&ranks);
+    list_box.set_sort_func(move |a, b| {
+        let ranks = ranks_clone.borrow();
+        ranks.get(a).cmp(&ranks.get(b)).into()
+    });
+    let mut rows = Vec::new();
+    let mut preselected_row = None;
+
+    let uris = shared_files.borrow().clone();
+    let remember_panel = match uris.as_slice() {
+        [uri] => Some(RememberPanel::new(uri)),
+        _ => None,
+    };
+
+    let desktop_files = resolve_desktop_files(cfg);
+    // the digits follow the rows, so only the choices with a row are counted
+    let choices = resolve_choices(cfg, &desktop_files)
+        .into_iter()
+        .filter_map(|choice| {
+            let desktop_file = desktop_files.get(&choice.desktop_file_id).cloned();
+            if desktop_file.is_none() {
+                warn!("no desktop file found for id: {}", choice.desktop_file_id);
+            }
+            desktop_file.map(|desktop_file| (choice, desktop_file))
+        })
+        .collect::<Vec<_>>();
+    let choices_len = choices.len();
+    let mut shortcuts = HashMap::new();
+
+    // Enter opens the last used application, or the daemon default one
+    let state = State::read();
+    let preselected_id = [
+        state.last_application_id,
+        state.default_application_id.filter(|_| daemon_mode),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|id| choices.iter().any(|(choice, _)| &choice.id == id));
+    for (idx, (choice, desktop_file)) in choices.into_iter().enumerate() {
+        let mut button_css_classes = vec![String::from("application")];
+        if idx == 0 {
+            button_css_classes.push("first".into());
+        } else if idx == choices_len - 1 {
+            button_css_classes.push("last".into());
+        }
+        if choice.options.action.is_some() {
+            button_css_classes.push("action".into());
+        }
+        let preselected = preselected_id.as_ref() == Some(&choice.id);
+        if preselected {
+            button_css_classes.push("preselected".into());
+        }
+        // the rows take the focus, so the arrows and Tab move the selection
+        let button = Button::builder()
+            .css_classes(button_css_classes)
+            .label(&choice.name)
+            .focusable(false)
+            .build();
+
+        let button_box = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(12)
+            .css_classes(vec![String::from("box")])
+            .build();
+        button.set_child(Some(&button_box));
+
+        if let Some(icon) = desktop_file.icon() {
+            let icon_image = Image::builder()
+                .gicon(&icon)
+                .css_classes(vec![String::from("icon")])
+                .pixel_size(48)
+                .margin_end(12)
+                .build();
+            button_box.append(&icon_image);
+        }
+
+        let label = Label::builder()
+            .label(&choice.name)
+            .css_classes(vec![String::from("label")])
+            .build();
+        button_box.append(&label);
+
+        if let Some(color) = &choice.color {
+            button_box.append(
+                &Label::builder()
+                    .use_markup(true)
+                    .label(format!("<span foreground=\"{}\">●</span>", color))
+                    .css_classes(vec![String::from("profile-color")])
+                    .build(),
+            );
+        }
+
+        if let Some(key) = choice.key {
+            button_box.append(
+                &Label::builder()
+                    .label(key.to_ascii_uppercase().to_string())
+                    .hexpand(true)
+                    .halign(Align::End)
+                    .css_classes(vec![String::from("key")])
+                    .build(),
+            );
+        }
+        let key = choice.key;
+        let open_private = key
+            .and_then(|_| private_action(&desktop_file))
+            .and_then(|action| {
+                let options = cfg
+                    .get_desktop_file(&choice.desktop_file_id)?
+                    .action_launch_options(&action);
+                let app = app.clone();
+                let desktop_files_tx = desktop_files_tx.clone();
+                let shared_files = Rc::clone(shared_files);
+                let desktop_file_id = choice.desktop_file_id.clone();
+                // a private window is not remembered, neither as a rule nor as the last used
+                let open_private: Rc<dyn Fn()> = Rc::new(move || {
+                    open_uris(
+                        &app,
+                        &desktop_files_tx,
+                        shared_files.borrow().clone(),
+                        &desktop_file_id,
+                        options.clone(),
+                        daemon_mode,
+                    )
+                });
+                Some(open_private)
+            });
+
+        let name_for_search = choice.name.clone();
+        let reference_for_closure = choice_reference(cfg, &choice);
+        let choice_id_for_closure = choice.id;
+        let remember_for_closure = remember_panel.clone();
+        let desktop_id_for_closure = choice.desktop_file_id;
+        let options_for_closure = choice.options;
+        let desktop_files_tx_for_closure = desktop_files_tx.clone();
+        let shared_uri_clone_active = Rc::clone(shared_files);
+        let app_for_closure = app.clone();
+        button.connect_clicked(move |_| {
+            let uris = shared_uri_clone_active.borrow().clone();
+            if let Some(remember) = remember_for_closure
+                .as_ref()
+                .and_then(|panel| panel.selected(&uris))
+            {
+                remember_choice(&reference_for_closure, &remember);
+            }
+            remember_last_used(&choice_id_for_closure);
+            open_uris(
+                &app_for_closure,
+                &desktop_files_tx_for_closure,
+                uris,
+                &desktop_id_for_closure,
+                options_for_closure.clone(),
+                daemon_mode,
+            );
+        });
+        let row = ListBoxRow::builder().child(&button).build();
+        let list_box_for_focus = list_box.clone();
+        let focus_controller = gtk::EventControllerFocus::new();
+        focus_controller.connect_enter(move |controller| {
+            if let Some(row) = controller.widget().and_downcast::<ListBoxRow>() {
+                list_box_for_focus.select_row(Some(&row));
+            }
+        });
+        row.add_controller(focus_controller);
+        list_box.append(&row);
+        if preselected {
+            list_box.select_row(Some(&row));
+            preselected_row = Some(row.clone());
+        }
+        if let Some(key) = key {
+            shortcuts.insert(
+                key,
+                Shortcut {
+                    row: row.clone(),
+                    open_private,
+                },
+            );
+        }
+        rows.push(PickerRow {
+            row,
+            label,
+            name: name_for_search,
+            terms: std::iter::once(desktop_file.name().to_string())
+                .chain(
+                    desktop_file
+                        .keywords()
+                        .iter()
+                        .map(|keyword| keyword.to_string()),
+                )
+                .collect(),
+        });
+    }
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .css_classes(vec!["main-box".to_string()])
+        .build();
+
+    // the links waiting for a choice can be edited, the edited ones are opened
+    if !uris.is_empty() {
+        let uris_box = Box::builder()
+            .orientation(Orientation::Vertical)
+            .css_classes(vec![String::from("uris")])
+            .build();
+        for (index, uri) in uris.iter().enumerate() {
+            uris_box.append(&build_uri_row(
+                app,
+                &list_box,
+                shared_files,
+                index,
+                uri,
+                remember_panel.clone(),
+                daemon_mode,
+            ));
+        }
+        content.append(&uris_box);
+    }
+
+    // letters typed in the window search the rows
+    let query_label = Label::builder()
+        .visible(false)
+        .halign(Align::Start)
+        .css_classes(vec![String::from("search")])
+        .build();
+    content.append(&query_label);
+
+    if choices_len == 0 {
+        let label = Label::builder()
+            .label("No desktop entries found or processed from the list.\nPlease check the paths in `DESKTOP_FILES` constant.")
+            .halign(Align::Center)
+            .valign(Align::Center)
+            .wrap(true)
+            .build();
+        content.append(&label);
+    } else {
+        content.append(&list_box);
+    }
+
+    if let Some(panel) = &remember_panel {
+        content.append(&panel.expander);
+    }
+
+    (
+        content,
+        Picker {
+            list_box,
+            rows,
+            shortcuts,
+            preselected: preselected_row,
+            query: String::new(),
+            query_label,
+            ranks,
+        },
+    )
+}
+
+/// Sends the uris to the desktop file opener, then the window is done.
+fn open_uris(
+    app: &Application,
+    desktop_files_tx: &Sender<DesktopFileOpenerCommand>,
+    uris: Vec<String>,
+    desktop_file_id: &str,
+    options: LaunchOptions,
+    daemon_mode: bool,
+) {
+    if let Err(e) = desktop_files_tx.send(DesktopFileOpenerCommand::Open(OpenParams {
+        uris,
+        desktop_file_id: desktop_file_id.to_string(),
+        options,
+        reply: None,
+    })) {
+        error!("failed to send command to desktop file opener: {}", e);
+    }
+    info!("after sending command, quitting the app");
+    if daemon_mode {
+        app.windows().iter().for_each(|window| window.hide());
+    } else {
+        app.quit();
+    }
+}
+
+/// Editable uri, with a button to copy it instead of opening it.
+fn build_uri_row(
+    app: &Application,
+    list_box: &ListBox,
+    shared_files: &Rc<RefCell<Vec<String>>>,
+    index: usize,
+    uri: &str,
+    remember_panel: Option<RememberPanel>,
+    daemon_mode: bool,
+) -> Box {
+    let entry = gtk::Entry::builder()
+        .text(uri)
+        .hexpand(true)
+        .css_classes(vec![String::from("uri")])
+        .build();
+    // Enter opens the edited uri with the selected application
+    let list_box_clone = list_box.clone();
+    entry.connect_activate(move |_| {
+        if let Some(row) = list_box_clone.selected_row() {
+            click_row(&row);
+        }
+    });
+    let shared_files_clone = Rc::clone(shared_files);
+    entry.connect_changed(move |entry| {
+        let uri = entry.text().to_string();
+        if let Some(panel) = &remember_panel {
+            panel.update(&uri);
+        }
+        if let Some(shared_uri) = shared_files_clone.borrow_mut().get_mut(index) {
+            *shared_uri = uri;
+        }
+    });
+
+    let copy_button = Button::builder()
+        .icon_name("edit-copy-symbolic")
+        .tooltip_text("Copy the link")
+        .css_classes(vec![String::from("copy")])
+        .build();
+    let entry_clone = entry.clone();
+    let app_clone = app.clone();
+    copy_button.connect_clicked(move |button| {
+        button.clipboard().set_text(&entry_clone.text());
+        info!("link copied to the clipboard");
+        // the clipboard content is served by this process, quitting in standalone mode would drop it
+        if daemon_mode {
+            app_clone.windows().iter().for_each(|window| window.hide());
+        }
+    });
+
+    let uri_box = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
+        .css_classes(vec![String::from("uri-box")])
+        .build();
+    uri_box.append(&entry);
+    uri_box.append(&copy_button);
+    uri_box
+}
+
+/// "Remember this choice" panel, only shown for a single uri.
+#[derive(Clone)]
+struct RememberPanel {
+    expander: Expander,
+    remember: CheckButton,
+    host: CheckButton,
+    prefix: CheckButton,
+}
+
+impl RememberPanel {
+    fn new(uri: &str) -> Self {
+        let panel_box = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .css_classes(vec![String::from("remember-box")])
+            .build();
+        let remember = CheckButton::builder()
+            .label("Always open with the chosen application")
+            .css_classes(vec![String::from("remember")])
+            .build();
+        panel_box.append(&remember);
+
+        let host_button = CheckButton::builder()
+            .active(true)
+            .sensitive(false)
+            .css_classes(vec![String::from("remember-host")])
+            .build();
+        let prefix_button = CheckButton::builder()
+            .label("links starting with this URL")
+            .group(&host_button)
+            .sensitive(false)
+            .css_classes(vec![String::from("remember-prefix")])
+            .build();
+        panel_box.append(&host_button);
+        panel_box.append(&prefix_button);
+
+        let host_clone = host_button.clone();
+        let prefix_clone = prefix_button.clone();
+        remember.connect_toggled(move |remember| {
+            host_clone.set_sensitive(remember.is_active());
+            prefix_clone.set_sensitive(remember.is_active());
+        });
+
+        let expander = Expander::builder()
+            .label("Remember this choice")
+            .child(&panel_box)
+            .css_classes(vec![String::from("remember-expander")])
+            .build();
+
+        let panel = Self {
+            expander,
+            remember,
+            host: host_button,
+            prefix: prefix_button,
+        };
+        panel.update(uri);
+        panel
+    }
+
+    /// Follows the uri entry, the host choice is hidden for uris without host.
+    fn update(&self, uri: &str) {
+        let host = uri_host(uri);
+        self.host.set_label(Some(&format!(
+            "every link of {}",
+            host.as_deref().unwrap_or_default()
+        )));
+        self.host.set_visible(host.is_some());
+        if host.is_none() {
+            self.prefix.set_active(true);
+        }
+    }
+
+    /// What to remember for the uri being opened, if the toggle is on.
+    fn selected(&self, uris: &[String]) -> Option<Remember> {
+        let [uri] = uris else {
+            return None;
+        };
+        if !self.remember.is_active() {
+            return None;
+        }
+        match uri_host(uri) {
+            Some(host) if self.host.is_active() => Some(Remember::Host(host)),
+            _ => Some(Remember::Prefix(uri.clone())),
+        }
+    }
+}
+
+fn uri_host(uri: &str) -> Option<String> {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+}
+
+/// Keeps the chosen application, it is preselected the next time.
+fn remember_last_used(choice_id: &str) {
+    let mut state = State::read();
+    state.last_application_id = Some(choice_id.to_string());
+    if let Err(e) = state.write() {
+        warn!("failed to write state: {}", e);
+    }
+}
+
+/// Adds a rule to the config, then asks the daemon to reload it.
+fn remember_choice(application: &str, choice: &Remember) {
+    let result = Config::path().and_then(|config_path| remember(&config_path, application, choice));
+    if let Err(e) = result {
+        error!("failed to remember choice: {}", e);
+        return;
+    }
+    // the daemon may be this process, its D-Bus thread must not wait for the UI one
+    std::thread::spawn(|| {
+        match DBUSClient::new()
+            .map_err(anyhow::Error::from)
+            .and_then(|dbus_client| dbus_client.reload())
+        {
+            Ok(_) => info!("daemon reloaded with the new rule"),
+            Err(e) => debug!("no daemon to reload: {}", e),
+        }
+    });
+}